@@ -0,0 +1,151 @@
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+
+/// Describes an axis-aligned bounding box by its minimum and maximum corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Default for Aabb {
+    /// The default Aabb is empty, so that it can be grown with `surrounding`.
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Display for Aabb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] - [{}]", self.min, self.max)
+    }
+}
+
+impl Aabb {
+    /// Builds a new Aabb from two opposite corners, given in any order.
+    pub fn build(a: Point3, b: Point3) -> Self {
+        Self {
+            min: Point3::build(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::build(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    /// Returns the point at the center of the Aabb.
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Constructs an Aabb containing nothing. Any box surrounding it is the other box.
+    pub const fn empty() -> Self {
+        Self {
+            min: Point3::build(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::build(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    /// Returns true if a Ray passes through the Aabb anywhere within the interval ray_t.
+    pub fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> bool {
+        let mut t_min = *ray_t.start();
+        let mut t_max = *ray_t.end();
+
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.dir[axis];
+            let mut t0 = (self.min[axis] - r.orig[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - r.orig[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns true if the Aabb contains no points.
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    /// Returns the index of the longest axis of the Aabb: 0 for x, 1 for y, 2 for z.
+    pub fn longest_axis(&self) -> usize {
+        let size = self.size();
+        if size.x() > size.y() && size.x() > size.z() {
+            0
+        } else if size.y() > size.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns the Aabb widened to at least delta along any axis where it is thinner,
+    /// so that flat objects still have a box with volume.
+    pub fn padded(&self, delta: f64) -> Self {
+        let mut bbox = *self;
+        for axis in 0..3 {
+            if bbox.max[axis] - bbox.min[axis] < delta {
+                bbox.min.e[axis] -= delta / 2.0;
+                bbox.max.e[axis] += delta / 2.0;
+            }
+        }
+        bbox
+    }
+
+    /// Returns the vector from the minimum to the maximum corner.
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Returns the surface area of the Aabb, or 0 if the Aabb is empty.
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let size = self.size();
+        2.0 * (size.x() * size.y() + size.y() * size.z() + size.z() * size.x())
+    }
+
+    /// Returns the smallest Aabb enclosing both Aabbs.
+    pub fn surrounding(&self, other: &Aabb) -> Self {
+        Self {
+            min: Point3::build(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3::build(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hit() {
+        let bbox = Aabb::build(
+            Point3::build(-1.0, -1.0, -1.0),
+            Point3::build(1.0, 1.0, 1.0),
+        );
+        let toward = Ray::build(Point3::build(0.0, 0.0, -5.0), Vec3::build(0.0, 0.0, 1.0));
+        let away = Ray::build(Point3::build(0.0, 0.0, -5.0), Vec3::build(0.0, 0.0, -1.0));
+        let past = Ray::build(Point3::build(0.0, 2.0, -5.0), Vec3::build(0.0, 0.0, 1.0));
+        assert!(bbox.hit(&toward, 0.0..=f64::INFINITY));
+        assert!(!bbox.hit(&toward, 0.0..=3.0));
+        assert!(!bbox.hit(&away, 0.0..=f64::INFINITY));
+        assert!(!bbox.hit(&past, 0.0..=f64::INFINITY));
+    }
+}
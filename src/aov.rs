@@ -0,0 +1,162 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::Color;
+use std::fmt::Display;
+
+/// Describes an arbitrary output variable: a property of the first surface seen
+/// through each pixel, rendered as an image of its own for debugging and compositing.
+/// Pixels that see no surface are black.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    Normal,    // Shading normal, mapped from -1..=1 to 0..=1 in each channel
+    Depth,     // Distance from the camera, in every channel
+    Albedo,    // Fraction of light the material scatters; black for pure emitters
+    Uv,        // Surface coordinates u and v, in the red and green channels
+    FrontFace, // Green where the outside of a surface is seen, red where the inside
+    ObjectId,  // A color picked at random for each Tagged object
+}
+
+impl Display for Aov {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Aov {
+    /// Every AOV, in the order they are listed to users.
+    pub const ALL: [Aov; 6] = [
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Albedo,
+        Aov::Uv,
+        Aov::FrontFace,
+        Aov::ObjectId,
+    ];
+
+    /// Returns the AOV with the given name, or None if there is no such AOV.
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+
+    /// Returns the name of the AOV, as used on the command line and in file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::FrontFace => "front_face",
+            Aov::ObjectId => "object_id",
+        }
+    }
+
+    /// Returns the value of the AOV for the first surface r hits in the world.
+    pub fn value(&self, r: &Ray, world: &dyn Hittable) -> Color {
+        let mut rec = HitRecord::new();
+        if !world.hit(r, 0.001..=f64::INFINITY, &mut rec) {
+            return Color::new();
+        }
+
+        match self {
+            Aov::Normal => 0.5 * (rec.normal + Color::build(1.0, 1.0, 1.0)),
+            Aov::Depth => {
+                let distance = rec.t * r.direction().length();
+                Color::build(distance, distance, distance)
+            }
+            Aov::Albedo => rec.mat.albedo(&rec),
+            Aov::Uv => Color::build(rec.u, rec.v, 0.0),
+            Aov::FrontFace => match rec.front_face {
+                true => Color::build(0.0, 1.0, 0.0),
+                false => Color::build(1.0, 0.0, 0.0),
+            },
+            Aov::ObjectId => id_color(rec.object_id),
+        }
+    }
+}
+
+/// Returns a color for an object ID, hashed with the SplitMix64 finalizer so that
+/// neighbouring IDs get unrelated colors. ID 0, for untagged objects, is white.
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::build(1.0, 1.0, 1.0);
+    }
+
+    let mut z = (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+
+    // Keep every channel away from black, so objects stand out from the background.
+    let channel = |shift: u32| 0.2 + 0.8 * ((z >> shift) & 0xff) as f64 / 255.0;
+    Color::build(channel(0), channel(8), channel(16))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable::Tagged;
+    use crate::hittable_list::HittableList;
+    use crate::material::{Lambertian, Metal};
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_aovs() {
+        let mut world = HittableList::new();
+        let mat = Arc::new(Lambertian::build(0.2, 0.4, 0.6));
+        let sphere = Arc::new(Sphere::build(Point3::new(), 1.0, mat));
+        world.add(Tagged::build(sphere, 7));
+
+        let r = Ray::build(Point3::build(0.0, 0.0, 5.0), Vec3::build(0.0, 0.0, -2.0));
+        let value = |aov: Aov| aov.value(&r, &world);
+        assert_eq!(value(Aov::Normal), Color::build(0.5, 0.5, 1.0));
+        assert_eq!(value(Aov::Depth), Color::build(4.0, 4.0, 4.0));
+        assert_eq!(value(Aov::Albedo), Color::build(0.2, 0.4, 0.6));
+        assert_eq!(value(Aov::FrontFace), Color::build(0.0, 1.0, 0.0));
+        assert_eq!(value(Aov::ObjectId), id_color(7));
+        assert_ne!(id_color(7), id_color(8));
+
+        let inside = Ray::build(Point3::new(), Vec3::build(0.0, 0.0, 1.0));
+        assert_eq!(
+            Aov::FrontFace.value(&inside, &world),
+            Color::build(1.0, 0.0, 0.0)
+        );
+        let miss = Ray::build(Point3::build(0.0, 5.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        assert_eq!(Aov::Depth.value(&miss, &world), Color::new());
+
+        for aov in Aov::ALL {
+            assert_eq!(Aov::from_name(aov.name()), Some(aov));
+        }
+        assert_eq!(Aov::from_name("motion"), None);
+    }
+
+    #[test]
+    fn test_untagged_hits() {
+        // An untagged object in front of a tagged one must not take on its ID.
+        let mut world = HittableList::new();
+        let fuzzy = Arc::new(Metal::build(0.9, 0.8, 0.7, 1.0));
+        let far = Arc::new(Sphere::build(
+            Point3::build(0.0, 0.0, -5.0),
+            1.0,
+            fuzzy.clone(),
+        ));
+        world.add(Tagged::build(far, 7));
+        world.add(Sphere::build(Point3::new(), 1.0, fuzzy));
+
+        // Grazing hits on fuzzy metal often scatter below the surface, but the albedo
+        // does not depend on the scattered direction.
+        let grazing = Ray::build(Point3::build(-5.0, 0.999, 0.0), Vec3::build(1.0, 0.0, 0.0));
+        for _ in 0..16 {
+            assert_eq!(
+                Aov::Albedo.value(&grazing, &world),
+                Color::build(0.9, 0.8, 0.7)
+            );
+        }
+
+        let r = Ray::build(Point3::build(0.0, 0.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        assert_eq!(Aov::ObjectId.value(&r, &world), id_color(0));
+    }
+}
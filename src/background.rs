@@ -0,0 +1,154 @@
+use crate::image::Image;
+use crate::vec3::{unit_vector, Color, Vec3};
+use std::f64::consts::PI;
+use std::fmt::Display;
+
+/// Describes the light arriving from the environment along rays that hit nothing.
+pub trait Background: Display + Send + Sync {
+    /// Returns the color seen looking along the given direction.
+    fn value(&self, direction: &Vec3) -> Color;
+}
+
+/// Describes a background of a single color in every direction.
+pub struct SolidBackground {
+    pub color: Color,
+}
+
+/// Describes a background that blends vertically between two colors, like a sky.
+pub struct GradientBackground {
+    pub bottom: Color, // Color looking straight down
+    pub top: Color,    // Color looking straight up
+}
+
+/// Describes a background lit by an equirectangular (latitude-longitude) image,
+/// typically a high-dynamic-range capture of a real environment.
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f64,  // Rotation about the vertical axis, in degrees
+    intensity: f64, // Multiplier applied to every value looked up in the image
+}
+
+impl Background for SolidBackground {
+    fn value(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+}
+
+impl Background for GradientBackground {
+    fn value(&self, direction: &Vec3) -> Color {
+        let unit_direction = unit_vector(direction);
+        let a = 0.5 * (unit_direction.y() + 1.0);
+        (1.0 - a) * self.bottom + a * self.top
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn value(&self, direction: &Vec3) -> Color {
+        if self.image.width == 0 || self.image.height == 0 {
+            return Color::new();
+        }
+
+        // Find the longitude (phi) around the vertical axis and the polar angle (theta)
+        // down from straight up, then map them across and down the image.
+        let d = unit_vector(direction);
+        let phi = d.x().atan2(-d.z()) + PI + self.rotation.to_radians();
+        let theta = d.y().clamp(-1.0, 1.0).acos();
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = theta / PI;
+
+        let i = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let j = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+        self.intensity * self.image.pixel(i, j)
+    }
+}
+
+impl Default for GradientBackground {
+    /// The default gradient is the blue-white sky from the book.
+    fn default() -> Self {
+        Self {
+            bottom: Color::build(1.0, 1.0, 1.0),
+            top: Color::build(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Display for SolidBackground {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.color)
+    }
+}
+
+impl Display for GradientBackground {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} - {}", self.bottom, self.top)
+    }
+}
+
+impl Display for EnvironmentMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} environment map",
+            self.image.width, self.image.height
+        )
+    }
+}
+
+impl SolidBackground {
+    /// Builds a new SolidBackground from RGB values.
+    pub fn build(r: f64, g: f64, b: f64) -> Self {
+        Self {
+            color: Color::build(r, g, b),
+        }
+    }
+}
+
+impl GradientBackground {
+    /// Builds a new GradientBackground from the colors looking down and up.
+    pub fn build(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+impl EnvironmentMap {
+    /// Builds a new EnvironmentMap from an equirectangular image of linear colors.
+    /// The rotation, in degrees, turns the environment about the vertical axis.
+    pub fn build(image: Image, rotation: f64, intensity: f64) -> Self {
+        Self {
+            image,
+            rotation,
+            intensity,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_environment_map() {
+        // Left half red, right half blue; the seam between them faces -z.
+        let mut image = Image::build(2, 1);
+        image.set_pixel(0, 0, Color::build(1.0, 0.0, 0.0), 1);
+        image.set_pixel(1, 0, Color::build(0.0, 0.0, 1.0), 1);
+
+        let env = EnvironmentMap::build(image.clone(), 0.0, 2.0);
+        assert_eq!(
+            env.value(&Vec3::build(-1.0, 0.0, 0.0)),
+            Color::build(2.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            env.value(&Vec3::build(1.0, 0.0, 0.0)),
+            Color::build(0.0, 0.0, 2.0)
+        );
+
+        let rotated = EnvironmentMap::build(image, 180.0, 1.0);
+        assert_eq!(
+            rotated.value(&Vec3::build(-1.0, 0.0, 0.0)),
+            Color::build(0.0, 0.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Number of buckets objects are binned into when evaluating SAH split candidates.
+const SAH_BUCKETS: usize = 12;
+
+/// Selects how a BvhNode divides its objects between its two children.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SplitHeuristic {
+    /// Split at the midpoint of the longest axis of the object centroids.
+    Midpoint,
+    /// Split where the Surface Area Heuristic estimates traversal is cheapest.
+    #[default]
+    Sah,
+}
+
+/// Describes a node of a bounding volume hierarchy. Rays only visit the children
+/// of a node when they pass through its bounding box.
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, ray_t.clone(), rec);
+        let right_t = *ray_t.start()..=if hit_left { rec.t } else { *ray_t.end() };
+        let hit_right = self.right.hit(r, right_t, rec);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return false;
+        }
+
+        let hit_left = self.left.shadow_hit(r, ray_t.clone(), rec);
+        let right_t = *ray_t.start()..=if hit_left { rec.t } else { *ray_t.end() };
+        let hit_right = self.right.shadow_hit(r, right_t, rec);
+
+        hit_left || hit_right
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> f64 {
+        if !self.bbox.hit(r, ray_t.clone()) {
+            return 1.0;
+        }
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return self.left.transmittance(r, ray_t);
+        }
+        self.left.transmittance(r, ray_t.clone()) * self.right.transmittance(r, ray_t)
+    }
+}
+
+/// For debugging. Prints the objects in each subtree of the node.
+impl Display for BvhNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if Arc::ptr_eq(&self.left, &self.right) {
+            write!(f, "{}", self.left)
+        } else {
+            write!(f, "{}\n{}", self.left, self.right)
+        }
+    }
+}
+
+impl BvhNode {
+    /// Builds a bounding volume hierarchy over the objects of a HittableList.
+    pub fn build(list: HittableList, heuristic: SplitHeuristic) -> Self {
+        let mut objects = list.objects;
+        if objects.is_empty() {
+            let empty: Arc<dyn Hittable> = Arc::new(HittableList::new());
+            return Self {
+                left: empty.clone(),
+                right: empty,
+                bbox: Aabb::empty(),
+            };
+        }
+        Self::from_objects(&mut objects, heuristic)
+    }
+
+    /// Recursively builds the node for a non-empty slice of objects.
+    fn from_objects(objects: &mut [Arc<dyn Hittable>], heuristic: SplitHeuristic) -> Self {
+        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match objects.len() {
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => (objects[0].clone(), objects[1].clone()),
+            _ => {
+                let mid = match heuristic {
+                    SplitHeuristic::Midpoint => Self::partition_midpoint(objects),
+                    SplitHeuristic::Sah => Self::partition_sah(objects),
+                };
+                let (lower, upper) = objects.split_at_mut(mid);
+                (
+                    Arc::new(Self::from_objects(lower, heuristic)),
+                    Arc::new(Self::from_objects(upper, heuristic)),
+                )
+            }
+        };
+
+        let bbox = left.bounding_box().surrounding(&right.bounding_box());
+        Self { left, right, bbox }
+    }
+
+    /// Returns the box enclosing the centroids of the objects' bounding boxes.
+    fn centroid_bounds(objects: &[Arc<dyn Hittable>]) -> Aabb {
+        objects.iter().fold(Aabb::empty(), |bounds, object| {
+            let c = object.bounding_box().centroid();
+            bounds.surrounding(&Aabb::build(c, c))
+        })
+    }
+
+    /// Reorders the objects about the middle of their centroids' longest axis and
+    /// returns the index of the first object in the upper half.
+    fn partition_midpoint(objects: &mut [Arc<dyn Hittable>]) -> usize {
+        let bounds = Self::centroid_bounds(objects);
+        let axis = bounds.longest_axis();
+        let split = bounds.centroid()[axis];
+
+        let mid = Self::partition(objects, |object| {
+            object.bounding_box().centroid()[axis] < split
+        });
+        if mid == 0 || mid == objects.len() {
+            // Every centroid landed on one side; fall back to an even split.
+            Self::partition_median(objects, axis)
+        } else {
+            mid
+        }
+    }
+
+    /// Reorders the objects at the bucket boundary with the lowest Surface Area
+    /// Heuristic cost and returns the index of the first object in the upper half.
+    fn partition_sah(objects: &mut [Arc<dyn Hittable>]) -> usize {
+        let bounds = Self::centroid_bounds(objects);
+        let axis = bounds.longest_axis();
+        let (lo, extent) = (bounds.min[axis], bounds.size()[axis]);
+        if extent <= 0.0 {
+            return Self::partition_median(objects, axis);
+        }
+
+        let bucket_of = |object: &Arc<dyn Hittable>| {
+            let offset = (object.bounding_box().centroid()[axis] - lo) / extent;
+            ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut boxes = [Aabb::empty(); SAH_BUCKETS];
+        for object in objects.iter() {
+            let b = bucket_of(object);
+            counts[b] += 1;
+            boxes[b] = boxes[b].surrounding(&object.bounding_box());
+        }
+
+        // Cost of splitting after bucket b is proportional to the area of each side
+        // weighted by the number of objects on that side.
+        let mut best = (f64::INFINITY, 0);
+        for b in 0..SAH_BUCKETS - 1 {
+            let (mut below, mut above) = (Aabb::empty(), Aabb::empty());
+            let (mut n_below, mut n_above) = (0, 0);
+            for i in 0..=b {
+                below = below.surrounding(&boxes[i]);
+                n_below += counts[i];
+            }
+            for i in b + 1..SAH_BUCKETS {
+                above = above.surrounding(&boxes[i]);
+                n_above += counts[i];
+            }
+            let cost =
+                n_below as f64 * below.surface_area() + n_above as f64 * above.surface_area();
+            if n_below > 0 && n_above > 0 && cost < best.0 {
+                best = (cost, b);
+            }
+        }
+
+        if best.0.is_infinite() {
+            return Self::partition_median(objects, axis);
+        }
+        Self::partition(objects, |object| bucket_of(object) <= best.1)
+    }
+
+    /// Sorts the objects along an axis and returns the index of the middle object.
+    fn partition_median(objects: &mut [Arc<dyn Hittable>], axis: usize) -> usize {
+        objects.sort_by(|a, b| {
+            let a = a.bounding_box().centroid()[axis];
+            let b = b.bounding_box().centroid()[axis];
+            a.total_cmp(&b)
+        });
+        objects.len() / 2
+    }
+
+    /// Moves the objects matching the predicate to the front of the slice and returns
+    /// how many there are.
+    fn partition(
+        objects: &mut [Arc<dyn Hittable>],
+        pred: impl Fn(&Arc<dyn Hittable>) -> bool,
+    ) -> usize {
+        let mut mid = 0;
+        for i in 0..objects.len() {
+            if pred(&objects[i]) {
+                objects.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+
+    /// Builds a grid-like scatter of small spheres receding down the -z axis.
+    fn spheres() -> HittableList {
+        let mat = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        let mut list = HittableList::new();
+        for i in 0..50 {
+            let center = Point3::build((i % 7) as f64 - 3.0, (i % 5) as f64 - 2.0, -(i as f64));
+            list.add(Sphere::build(center, 0.4, mat.clone()));
+        }
+        list
+    }
+
+    /// A BVH must report the same closest hits as the list it was built from.
+    #[test]
+    fn test_matches_list() {
+        let list = spheres();
+        for heuristic in [SplitHeuristic::Midpoint, SplitHeuristic::Sah] {
+            let bvh = BvhNode::build(spheres(), heuristic);
+            for i in 0..100 {
+                let dir = Vec3::build(
+                    (i % 10) as f64 * 0.05 - 0.25,
+                    (i / 10) as f64 * 0.05 - 0.25,
+                    -1.0,
+                );
+                let r = Ray::build(Point3::build(0.0, 0.0, 5.0), dir);
+
+                let (mut rec_list, mut rec_bvh) = (HitRecord::new(), HitRecord::new());
+                let hit_list = list.hit(&r, 0.001..=f64::INFINITY, &mut rec_list);
+                let hit_bvh = bvh.hit(&r, 0.001..=f64::INFINITY, &mut rec_bvh);
+                assert_eq!(hit_list, hit_bvh);
+                if hit_list {
+                    assert_eq!(rec_list.t, rec_bvh.t);
+                }
+            }
+        }
+    }
+}
@@ -1,29 +1,66 @@
-use crate::hittable::{HitRecord, Hittable};
-use crate::random_f64;
+use crate::aov::Aov;
+use crate::background::{Background, GradientBackground};
+use crate::hittable::Hittable;
+use crate::image::Image;
+use crate::image_io;
+use crate::integrator::{Integrator, PathIntegrator};
 use crate::ray::Ray;
-use crate::vec3::{cross, random_vec3_in_unit_disk, unit_vector, write_color, Color, Point3, Vec3};
+use crate::sampler::{self, PixelSample, RandomSampler, Sampler};
+use crate::seed_random;
+use crate::vec3::{cross, unit_disk_point, unit_vector, Color, Point3, Vec3};
+use crate::volume::HeightFog;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 pub struct Camera {
-    pub aspect_ratio: f64,      // Ratio of image width over height
-    pub image_width: i32,       // Rendered image width in pixel count
-    pub samples_per_pixel: i32, // Count of random samples for each pixel.
-    pub max_depth: i32,         // Maximum number of ray bounces into scene.
-    pub look_from: Point3,      // Point camera is looking from
-    pub look_at: Point3,        // Point camera is looking at
-    pub vup: Vec3,              // Camera-relative "up" direction
-    pub vfov: f64,              // Vertical view angle (field of view)
-    pub defocus_angle: f64,     // Variation angle of rays through each pixel
-    pub focus_dist: f64,        // Distance from camera look_from point to plane of perfect focus
-    image_height: i32,          // Rendered image height
-    center: Point3,             // Camera center
-    pixel100_loc: Point3,       // Location of pixel 0, 0
-    pixel_delta_u: Vec3,        // Offset to pixel to the right
-    pixel_delta_v: Vec3,        // Offset to pixel below
-    u: Vec3,                    // Camera frame basis vectors
-    v: Vec3,                    //
-    w: Vec3,                    //
-    defocus_disk_u: Vec3,       // Defocus disk horizontal radius
-    defocus_disk_v: Vec3,       // Defocus disk vertical radius
+    pub aspect_ratio: f64,          // Ratio of image width over height
+    pub image_width: i32,           // Rendered image width in pixel count
+    pub samples_per_pixel: i32,     // Count of random samples for each pixel.
+    pub adaptive_threshold: f64,    // Error at which a pixel stops sampling (0 samples uniformly)
+    pub min_samples_per_pixel: i32, // Samples every pixel takes when sampling adaptively
+    pub max_samples_per_pixel: i32, // Most samples a pixel takes when sampling adaptively
+    pub max_depth: i32,             // Maximum number of ray bounces into scene.
+    pub roulette_depth: i32,        // Bounces before Russian roulette may end a path
+    pub look_from: Point3,          // Point camera is looking from
+    pub look_at: Point3,            // Point camera is looking at
+    pub vup: Vec3,                  // Camera-relative "up" direction
+    pub vfov: f64,                  // Vertical view angle (field of view)
+    pub defocus_angle: f64,         // Variation angle of rays through each pixel
+    pub focus_dist: f64, // Distance from camera look_from point to plane of perfect focus
+    pub threads: usize,  // Worker thread count (0 uses all available cores)
+    pub seed: u64,       // Seed for the random sampling of every pixel
+    pub shutter_open: f64, // Time the shutter opens; rays are cast between the two
+    pub shutter_close: f64, // Time the shutter closes
+    // Light arriving along rays that hit nothing
+    pub background: Arc<dyn Background>,
+    // Optional fog filling the whole scene, thinning with height
+    pub fog: Option<HeightFog>,
+    // Light transport algorithm finding the color seen along each camera ray
+    pub integrator: Arc<dyn Integrator>,
+    // Source of the pixel, lens, shutter and bounce dimensions of each sample
+    pub sampler: Arc<dyn Sampler>,
+    image_columns: i32,   // Rendered image width, at least 1
+    image_height: i32,    // Rendered image height
+    center: Point3,       // Camera center
+    pixel100_loc: Point3, // Location of pixel 0, 0
+    pixel_delta_u: Vec3,  // Offset to pixel to the right
+    pixel_delta_v: Vec3,  // Offset to pixel below
+    u: Vec3,              // Camera frame basis vectors
+    v: Vec3,              //
+    w: Vec3,              //
+    defocus_disk_u: Vec3, // Defocus disk horizontal radius
+    defocus_disk_v: Vec3, // Defocus disk vertical radius
+}
+
+/// Describes the running totals of the samples taken in a pixel.
+#[derive(Clone, Copy, Default)]
+struct PixelStats {
+    sum: Color,        // Sum of the sample colors
+    luminance: f64,    // Sum of the sample luminances
+    luminance_sq: f64, // Sum of the squared sample luminances
+    count: u32,
 }
 
 impl Default for Camera {
@@ -32,14 +69,23 @@ impl Default for Camera {
         let image_width = 100;
         let image_height = 100;
         let samples_per_pixel = 10;
+        let adaptive_threshold = 0.0;
+        let min_samples_per_pixel = 16;
+        let max_samples_per_pixel = 1024;
         let max_depth = 10;
+        let roulette_depth = 3;
         let vfov = 90.0_f64;
         let look_from = Point3::build(0.0, 0.0, -1.0);
         let look_at = Point3::new();
         let center = look_from;
         let vup = Vec3::build(0.0, 1.0, 0.0);
-        let defocus_angle = 0.0;
+        let defocus_angle = 0.0_f64;
         let focus_dist = 10.0;
+        let threads = 0;
+        let seed = 0;
+        let shutter_open = 0.0;
+        let shutter_close = 0.0;
+        let background = Arc::new(GradientBackground::default());
 
         // Determine viewport dimensions.
         let theta = vfov.to_radians();
@@ -65,16 +111,21 @@ impl Default for Camera {
         let pixel100_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius = focus_dist * (((defocus_angle / 2.0) as f64).to_radians()).tan();
+        let defocus_radius = focus_dist * ((defocus_angle / 2.0).to_radians()).tan();
         let defocus_disk_u = u * defocus_radius;
         let defocus_disk_v = v * defocus_radius;
 
         Camera {
             aspect_ratio,
+            image_columns: image_width,
             image_height,
             image_width,
             samples_per_pixel,
+            adaptive_threshold,
+            min_samples_per_pixel,
+            max_samples_per_pixel,
             max_depth,
+            roulette_depth,
             look_from,
             look_at,
             vup,
@@ -90,19 +141,29 @@ impl Default for Camera {
             focus_dist,
             defocus_disk_u,
             defocus_disk_v,
+            threads,
+            seed,
+            shutter_open,
+            shutter_close,
+            background,
+            fog: None,
+            integrator: Arc::new(PathIntegrator),
+            sampler: Arc::new(RandomSampler),
         }
     }
 }
 
 impl Camera {
-    /// Returns a random point in the camera defocus disk.
+    /// Returns a point in the camera defocus disk, placed by the next two dimensions
+    /// of the current sample.
     fn defocus_disk_sample(&self) -> Point3 {
-        let p = random_vec3_in_unit_disk();
+        let (r1, r2) = sampler::sample_2d();
+        let p = unit_disk_point(r1, r2);
         self.center + (p.e[0] * self.defocus_disk_u) + (p.e[1] * self.defocus_disk_v)
     }
 
-    /// Get a randomly-sampled camera ray for the pixel at location i,j, originating from
-    /// the camera defocus disk.
+    /// Get a sampled camera ray for the pixel at location i,j, originating from the
+    /// camera defocus disk and cast at a sampled time while the shutter is open.
     fn get_ray(&self, i: i32, j: i32) -> Ray {
         let pixel_center = self.pixel100_loc + (self.pixel_delta_u * i) + (self.pixel_delta_v * j);
         let pixel_sample = pixel_center + self.pixel_sample_square();
@@ -113,16 +174,19 @@ impl Camera {
             self.defocus_disk_sample()
         };
         let ray_direction = pixel_sample - ray_origin;
+        let ray_time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler::sample_1d()
+        } else {
+            self.shutter_open
+        };
 
-        Ray {
-            orig: ray_origin,
-            dir: ray_direction,
-        }
+        Ray::build_timed(ray_origin, ray_direction, ray_time)
     }
 
     /// Calculates and sets the derived fields of the Camera struct.
     fn initialize(&mut self) {
-        self.image_height = (self.image_width as f64 / self.aspect_ratio) as i32;
+        self.image_columns = self.image_width.max(1);
+        self.image_height = (self.image_columns as f64 / self.aspect_ratio) as i32;
         self.image_height = if self.image_height < 1 {
             1
         } else {
@@ -135,7 +199,8 @@ impl Camera {
         let theta = self.vfov.to_radians();
         let h = (theta / 2.0).tan();
         let viewport_height = 2.0 * h * self.focus_dist;
-        let viewport_width = viewport_height * (self.image_width as f64 / self.image_height as f64);
+        let viewport_width =
+            viewport_height * (self.image_columns as f64 / self.image_height as f64);
 
         // Calculate the u, v, w unit basis vectors fro the camera coordinate frame.
         self.w = unit_vector(&(self.look_from - self.look_at));
@@ -147,7 +212,7 @@ impl Camera {
         let viewport_v = viewport_height * -self.v; // Vector down viewport vertical edge
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        self.pixel_delta_u = viewport_u / (self.image_width as f64);
+        self.pixel_delta_u = viewport_u / (self.image_columns as f64);
         self.pixel_delta_v = viewport_v / (self.image_height as f64);
 
         // Calculate the location of the upper left pixel.
@@ -156,8 +221,7 @@ impl Camera {
         self.pixel100_loc = viewport_upper_left + 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius =
-            self.focus_dist * (((self.defocus_angle / 2.0) as f64).to_radians()).tan();
+        let defocus_radius = self.focus_dist * ((self.defocus_angle / 2.0).to_radians()).tan();
         self.defocus_disk_u = self.u * defocus_radius;
         self.defocus_disk_v = self.v * defocus_radius;
     }
@@ -167,53 +231,488 @@ impl Camera {
         Camera::default()
     }
 
-    /// Returns a random point in the square surrounging a pixel at the origin.
+    /// Returns a point in the square surrounging a pixel at the origin, placed by the
+    /// next two dimensions of the current sample.
     fn pixel_sample_square(&self) -> Vec3 {
-        let px = -0.5 + random_f64();
-        let py = -0.5 + random_f64();
+        let (r1, r2) = sampler::sample_2d();
+        let px = -0.5 + r1;
+        let py = -0.5 + r2;
         (px * self.pixel_delta_u) + (py * self.pixel_delta_v)
     }
 
-    /// Renders the output image.
-    pub fn render(&mut self, world: &dyn Hittable) {
+    /// Renders the output image and prints it to stdout as a PPM file.
+    pub fn render(&mut self, world: &dyn Hittable, lights: &dyn Hittable) {
+        let image = self.render_image(world, lights);
+        image_io::write_p3(&image, &mut io::stdout().lock())
+            .expect("failed to write image to stdout");
+        eprintln!("\rDone.\n");
+    }
+
+    /// Renders an AOV image of the world into memory. One ray is cast through the
+    /// center of each pixel, from the camera center at the time the shutter opens, so
+    /// that values are exact rather than blended across edges.
+    pub fn render_aov(&mut self, world: &dyn Hittable, aov: Aov) -> Image {
+        self.render_pixels(|cam, i, j| {
+            let pixel_center = cam.pixel100_loc + (cam.pixel_delta_u * i) + (cam.pixel_delta_v * j);
+            let r = Ray::build_timed(cam.center, pixel_center - cam.center, cam.shutter_open);
+            // Rays pass through participating media at random depths, so seed per pixel
+            // to keep renders identical however scanlines are scheduled.
+            seed_random(cam.pixel_seed(i, j, 0));
+            (aov.value(&r, world), 1)
+        })
+    }
+
+    /// Renders the output image into memory. The lights are the emitters in the world
+    /// worth sampling directly; pass an empty HittableList to rely on scattered rays
+    /// finding the lights by chance.
+    pub fn render_image(&mut self, world: &dyn Hittable, lights: &dyn Hittable) -> Image {
+        if self.adaptive_threshold > 0.0 {
+            return self.render_adaptive(world, lights);
+        }
+        self.render_pixels(|cam, i, j| {
+            let mut stats = PixelStats::default();
+            cam.sample_pixel(
+                i,
+                j,
+                0,
+                cam.samples_per_pixel.max(1) as u32,
+                world,
+                lights,
+                &mut stats,
+            );
+            (stats.mean(), stats.count)
+        })
+    }
+
+    /// Renders the output image into memory, sampling each pixel until it converges.
+    /// Every pixel first takes min_samples_per_pixel samples. Then, pass by pass, the
+    /// pixels whose error is still above adaptive_threshold take that many samples
+    /// more, noisiest first, until the budget of samples_per_pixel samples for each
+    /// pixel in the image is spent, every pixel has converged, or the unconverged
+    /// pixels have reached max_samples_per_pixel.
+    fn render_adaptive(&mut self, world: &dyn Hittable, lights: &dyn Hittable) -> Image {
         self.initialize();
+        let cam = &*self;
 
-        println!("P3\n{} {} \n255", self.image_width, self.image_height);
+        let width = cam.image_columns as usize;
+        let height = cam.image_height as usize;
+        let batch = cam.min_samples_per_pixel.max(1) as u32;
+        let max_samples = (cam.max_samples_per_pixel.max(1) as u32).max(batch);
+        let mut budget = (cam.samples_per_pixel.max(1) as u64) * (width * height) as u64;
 
-        for j in 0..self.image_height {
-            eprintln!("\rScanlines remaining: {}", self.image_height - j);
-            for i in 0..self.image_width {
-                let mut pixel_color = Color::new();
-                for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += Self::ray_color(&r, self.max_depth, world);
+        let mut stats = vec![PixelStats::default(); width * height];
+        let mut pending: Vec<bool> = vec![true; width * height];
+        for pass in 0.. {
+            cam.for_each_scanline(&mut stats, &mut pending, |j, stats, pending| {
+                for (i, (stats, pending)) in stats.iter_mut().zip(pending).enumerate() {
+                    if *pending {
+                        cam.sample_pixel(i as i32, j, pass, batch, world, lights, stats);
+                    }
                 }
-                println!("{}", write_color(&pixel_color, self.samples_per_pixel));
+            });
+            let taken = pending.iter().filter(|&&p| p).count() as u64 * batch as u64;
+            budget = budget.saturating_sub(taken);
+
+            // Pick the noisiest unconverged pixels that the budget can still afford.
+            let mut noisy: Vec<(usize, f64)> = stats
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.count + batch <= max_samples)
+                .map(|(index, s)| (index, s.error()))
+                .filter(|&(_, error)| error > cam.adaptive_threshold)
+                .collect();
+            noisy.sort_by(|a, b| b.1.total_cmp(&a.1));
+            noisy.truncate((budget / batch as u64) as usize);
+            eprintln!("\rPass {}: {} pixels left to converge", pass, noisy.len());
+            if noisy.is_empty() {
+                break;
+            }
+
+            pending.fill(false);
+            for (index, _) in noisy {
+                pending[index] = true;
             }
         }
-        eprintln!("\rDone.\n");
+
+        let mut image = Image::build(width, height);
+        for (index, s) in stats.iter().enumerate() {
+            image.pixels[index] = s.mean();
+            image.samples[index] = s.count;
+        }
+        image
     }
 
-    /// Determines the color returned by a Ray when cast into the world.
-    fn ray_color(r: &Ray, depth: i32, world: &dyn Hittable) -> Color {
-        let mut rec = HitRecord::new();
+    /// Renders an image into memory, finding the linear color and sample count of the
+    /// pixel at location i,j with pixel.
+    fn render_pixels(&mut self, pixel: impl Fn(&Camera, i32, i32) -> (Color, u32) + Sync) -> Image {
+        self.initialize();
+        let cam = &*self;
 
-        //If we've exceeded the ray bounce limit, no more light is gathered.
-        if depth <= 0 {
-            return Color::new();
-        }
+        let width = cam.image_columns as usize;
+        let height = cam.image_height as usize;
+        let mut image = Image::build(width, height);
+        let remaining = AtomicUsize::new(height);
 
-        if world.hit(r, 0.001..=f64::INFINITY, &mut rec) {
-            let mut scattered: Ray = Ray::new();
-            let mut attenuation: Color = Color::new();
-            if rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
-                return attenuation * Camera::ray_color(&scattered, depth - 1, world);
+        cam.for_each_scanline(
+            &mut image.pixels,
+            &mut image.samples,
+            |j, pixels, samples| {
+                for (i, (pixel_color, sample_count)) in pixels.iter_mut().zip(samples).enumerate() {
+                    (*pixel_color, *sample_count) = pixel(cam, i as i32, j);
+                }
+
+                let left = remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+                eprintln!("\rScanlines remaining: {}", left);
+            },
+        );
+
+        image
+    }
+
+    /// Calls f with the index j and the items of every scanline of two row-major
+    /// buffers of per-pixel items.
+    /// Scanlines are handed out to a pool of worker threads as they become free, and
+    /// each worker writes its results straight into the buffers.
+    fn for_each_scanline<A: Send, B: Send>(
+        &self,
+        a: &mut [A],
+        b: &mut [B],
+        f: impl Fn(i32, &mut [A], &mut [B]) + Sync,
+    ) {
+        let width = self.image_columns as usize;
+        let height = self.image_height as usize;
+        let threads = self.thread_count().min(height);
+        let scanlines = Mutex::new(a.chunks_mut(width).zip(b.chunks_mut(width)).enumerate());
+
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| loop {
+                    // Hold the lock only long enough to claim the next scanline.
+                    let next = scanlines.lock().unwrap().next();
+                    let Some((j, (a, b))) = next else {
+                        break;
+                    };
+                    f(j as i32, a, b);
+                });
             }
+        });
+    }
+
+    /// Adds count samples cast through the pixel at location i,j to its stats, taking
+    /// their dimensions from the camera's sampler. The samples continue the pixel's
+    /// sequence from those already taken, while the random numbers are seeded from the
+    /// pixel and the pass, so that each pass over the pixel draws fresh ones.
+    #[allow(clippy::too_many_arguments)]
+    fn sample_pixel(
+        &self,
+        i: i32,
+        j: i32,
+        pass: u64,
+        count: u32,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        stats: &mut PixelStats,
+    ) {
+        seed_random(self.pixel_seed(i, j, pass));
+        let (seed, first) = (self.pixel_seed(i, j, 0), stats.count);
+        for index in first..first + count {
+            let sample = PixelSample {
+                i,
+                j,
+                seed,
+                index,
+                count,
+            };
+            sampler::begin_sample(&self.sampler, sample);
+            let r = self.get_ray(i, j);
+            stats.add(self.integrator.ray_color(&r, self, world, lights));
+        }
+        sampler::end_samples();
+    }
+
+    /// Returns the random seed for a pass over the pixel at location i,j, mixing the
+    /// camera seed, the pixel index and the pass with the SplitMix64 finalizer.
+    fn pixel_seed(&self, i: i32, j: i32, pass: u64) -> u64 {
+        let index = j as u64 * self.image_columns as u64 + i as u64;
+        let mut z = self.seed
+            ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ pass.wrapping_mul(0xd6e8_feb8_6659_fd93);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns the number of worker threads to render with.
+    fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
+    }
+}
+
+impl PixelStats {
+    /// Adds a sample to the totals.
+    fn add(&mut self, color: Color) {
+        let y = 0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z();
+        self.sum += color;
+        self.luminance += y;
+        self.luminance_sq += y * y;
+        self.count += 1;
+    }
+
+    /// Returns the relative standard error of the pixel's mean luminance: how far the
+    /// mean is likely to be from the converged value, as a fraction of it. Dark pixels
+    /// are measured against a floor of 0.01, so that noise too faint to see converges.
+    fn error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.count as f64;
+        let mean = self.luminance / n;
+        let variance = (self.luminance_sq / n - mean * mean).max(0.0) * n / (n - 1.0);
+        (variance / n).sqrt() / mean.max(0.01)
+    }
+
+    /// Returns the mean color of the samples, or black if there are none.
+    fn mean(&self) -> Color {
+        if self.count == 0 {
             return Color::new();
         }
+        self.sum / self.count as f64
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::background::SolidBackground;
+    use crate::hittable_list::HittableList;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+
+    fn test_world() -> HittableList {
+        let mut world = HittableList::new();
+        let mat = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        world.add(Sphere::build(Point3::build(0.0, 0.0, 0.0), 0.5, mat));
+        world
+    }
+
+    /// Returns a camera looking down at a floor under a small light, as a world and
+    /// the list holding the light.
+    pub(crate) fn lit_floor() -> (Camera, HittableList, HittableList) {
+        let mut world = HittableList::new();
+        let floor = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        world.add(Quad::build(
+            Point3::build(-5.0, 0.0, 5.0),
+            Vec3::build(10.0, 0.0, 0.0),
+            Vec3::build(0.0, 0.0, -10.0),
+            floor,
+        ));
+        let lamp = Arc::new(DiffuseLight::build(50.0, 50.0, 50.0));
+        let light: Arc<dyn Hittable> = Arc::new(Quad::build(
+            Point3::build(-0.25, 2.0, -0.25),
+            Vec3::build(0.5, 0.0, 0.0),
+            Vec3::build(0.0, 0.0, 0.5),
+            lamp,
+        ));
+        world.objects.push(light.clone());
+        let mut lights = HittableList::new();
+        lights.objects.push(light);
+
+        let mut cam = Camera::new();
+        cam.image_width = 8;
+        cam.samples_per_pixel = 64;
+        cam.look_from = Point3::build(0.0, 1.0, 0.0);
+        cam.look_at = Point3::new();
+        cam.vup = Vec3::build(0.0, 0.0, -1.0);
+        cam.vfov = 60.0;
+        cam.background = Arc::new(SolidBackground::build(0.0, 0.0, 0.0));
+        (cam, world, lights)
+    }
 
-        let unit_direction = unit_vector(&r.direction());
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::build(1.0, 1.0, 1.0) + a * Color::build(0.5, 0.7, 1.0)
+    #[test]
+    fn test_render_image() {
+        let world = test_world();
+        let mut cam = Camera::new();
+        cam.aspect_ratio = 2.0;
+        cam.image_width = 16;
+        cam.samples_per_pixel = 3;
+        cam.threads = 2;
+
+        let image = cam.render_image(&world, &HittableList::new());
+        assert_eq!((image.width, image.height), (16, 8));
+        assert_eq!(image.pixels.len(), 16 * 8);
+        assert!(image.samples.iter().all(|&n| n == 3));
+
+        // Every image has at least one pixel, and every pixel takes at least one sample.
+        cam.image_width = -5;
+        cam.samples_per_pixel = -3;
+        let image = cam.render_image(&world, &HittableList::new());
+        assert_eq!((image.width, image.height), (1, 1));
+        assert!(image.samples.iter().all(|&n| n == 1));
+        assert_eq!((cam.image_width, cam.samples_per_pixel), (-5, -3));
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        let world = test_world();
+        let mut cam = Camera::new();
+        cam.aspect_ratio = 2.0;
+        cam.image_width = 16;
+        cam.samples_per_pixel = 32;
+        cam.adaptive_threshold = 0.01;
+        cam.min_samples_per_pixel = 8;
+        cam.max_samples_per_pixel = 256;
+        cam.threads = 1;
+
+        // Sky pixels converge at once; pixels on the diffuse sphere take more samples.
+        let image = cam.render_image(&world, &HittableList::new());
+        assert_eq!(image.samples[0], 8);
+        assert!(image.samples[4 * 16 + 8] > 8);
+        assert!(image.samples.iter().all(|&n| (8..=256).contains(&n)));
+        let total: u32 = image.samples.iter().sum();
+        assert!(total <= 32 * 16 * 8);
+
+        cam.threads = 4;
+        let again = cam.render_image(&world, &HittableList::new());
+        assert_eq!(again.pixels, image.pixels);
+        assert_eq!(again.samples, image.samples);
+    }
+
+    /// The edge of a glowing disk on black is only found by where samples land in
+    /// each pixel, so samplers that spread them evenly find it more accurately.
+    #[test]
+    fn test_samplers() {
+        let mut world = HittableList::new();
+        let lamp = Arc::new(DiffuseLight::build(1.0, 1.0, 1.0));
+        world.add(Sphere::build(Point3::new(), 0.4, lamp));
+        let mut cam = Camera::new();
+        cam.image_width = 16;
+        cam.background = Arc::new(SolidBackground::build(0.0, 0.0, 0.0));
+
+        let mut render = |name: &str, samples_per_pixel: i32| {
+            cam.sampler = sampler::named(name).unwrap();
+            cam.samples_per_pixel = samples_per_pixel;
+            cam.render_image(&world, &HittableList::new()).pixels
+        };
+        let reference = render("sobol", 4096);
+        let mut error = |name: &str| {
+            let pixels = render(name, 16);
+            let sum: f64 = (pixels.iter().zip(&reference))
+                .map(|(p, q)| (p.x() - q.x()).powi(2))
+                .sum();
+            (sum / pixels.len() as f64).sqrt()
+        };
+
+        let random = error("random");
+        for name in ["stratified", "halton", "sobol", "blue_noise"] {
+            let other = error(name);
+            assert!(other < 0.7 * random, "{}: {} vs {}", name, other, random);
+        }
+    }
+
+    #[test]
+    fn test_render_aov() {
+        let world = test_world();
+        let mut cam = Camera::new();
+        cam.image_width = 5;
+
+        let image = cam.render_aov(&world, Aov::Depth);
+        assert_eq!(image.pixels[2 * 5 + 2], Color::build(0.5, 0.5, 0.5));
+        assert_eq!(image.pixels[0], Color::new());
+        assert!(image.samples.iter().all(|&n| n == 1));
+    }
+
+    /// The same seed must give the same image regardless of the thread count.
+    #[test]
+    fn test_render_is_deterministic() {
+        let world = test_world();
+        let mut cam = Camera::new();
+        cam.image_width = 12;
+        cam.samples_per_pixel = 4;
+        cam.seed = 7;
+
+        cam.threads = 1;
+        let serial = cam.render_image(&world, &HittableList::new());
+        cam.threads = 3;
+        let parallel = cam.render_image(&world, &HittableList::new());
+        assert_eq!(serial, parallel);
+
+        cam.seed = 8;
+        assert_ne!(serial, cam.render_image(&world, &HittableList::new()));
+    }
+
+    /// A camera enclosed by a light sees only the light's emitted color.
+    #[test]
+    fn test_emission() {
+        let mut world = HittableList::new();
+        let light = Arc::new(DiffuseLight::build(1.0, 2.0, 4.0));
+        world.add(Sphere::build(Point3::new(), 10.0, light));
+
+        let mut cam = Camera::new();
+        cam.image_width = 4;
+        cam.samples_per_pixel = 4;
+        let image = cam.render_image(&world, &HittableList::new());
+        assert!(image
+            .pixels
+            .iter()
+            .all(|&p| p == Color::build(1.0, 2.0, 4.0)));
+    }
+
+    /// Sampling a small light directly converges on the same image as waiting for
+    /// scattered rays to find it, with far less noise.
+    #[test]
+    fn test_light_sampling() {
+        let (mut cam, world, lights) = lit_floor();
+        cam.max_depth = 2;
+
+        let brightness =
+            |image: &Image| -> Vec<f64> { image.pixels.iter().map(|p| p.x()).collect() };
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let sampled = brightness(&cam.render_image(&world, &lights));
+        let unsampled = brightness(&cam.render_image(&world, &HittableList::new()));
+        assert!((mean(&sampled) / mean(&unsampled) - 1.0).abs() < 0.1);
+
+        // The floor is lit smoothly, so neighbouring pixels differ mostly by noise.
+        let roughness =
+            |values: &[f64]| -> f64 { values.windows(2).map(|w| (w[0] - w[1]).abs()).sum::<f64>() };
+        assert!(roughness(&sampled) * 2.0 < roughness(&unsampled));
+    }
+
+    /// Russian roulette ends most paths early without darkening or brightening the
+    /// image. Inside a white room light bounces many times before fading.
+    #[test]
+    fn test_russian_roulette() {
+        let mut world = HittableList::new();
+        let wall = Arc::new(Lambertian::build(0.8, 0.8, 0.8));
+        world.add(Sphere::build(Point3::new(), 10.0, wall));
+        let lamp = Arc::new(DiffuseLight::build(20.0, 20.0, 20.0));
+        let light: Arc<dyn Hittable> =
+            Arc::new(Sphere::build(Point3::build(0.0, 6.0, 0.0), 1.0, lamp));
+        world.objects.push(light.clone());
+        let mut lights = HittableList::new();
+        lights.objects.push(light);
+
+        let mut cam = Camera::new();
+        cam.image_width = 8;
+        cam.samples_per_pixel = 64;
+        cam.max_depth = 50;
+        cam.look_from = Point3::build(0.0, -5.0, 0.0);
+        cam.look_at = Point3::build(0.0, -10.0, 0.0);
+        cam.vup = Vec3::build(0.0, 0.0, 1.0);
+
+        let mean = |image: &Image| {
+            image.pixels.iter().map(|p| p.x()).sum::<f64>() / image.pixels.len() as f64
+        };
+        cam.roulette_depth = cam.max_depth;
+        let full = mean(&cam.render_image(&world, &lights));
+        cam.roulette_depth = 1;
+        let roulette = mean(&cam.render_image(&world, &lights));
+        assert!((roulette / full - 1.0).abs() < 0.05);
     }
 }
@@ -0,0 +1,186 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Isotropic, Material};
+use crate::random_f64;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Describes a volume of constant density, such as smoke or fog, filling a convex
+/// boundary. Rays passing through it scatter at random depths, more often the denser
+/// it is, in directions chosen by its phase function.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        let Some((t_enter, t_exit)) = span_inside(self.boundary.as_ref(), r, ray_t) else {
+            return false;
+        };
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_f64().ln();
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = t_enter + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+        rec.normal = Vec3::build(1.0, 0.0, 0.0); // arbitrary
+        rec.front_face = true; // also arbitrary
+        rec.mat = Arc::clone(&self.phase_function);
+        rec.object_id = 0;
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    /// Shadow rays pass through the medium, dimmed by its transmittance.
+    fn shadow_hit(&self, _r: &Ray, _ray_t: RangeInclusive<f64>, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    /// Returns the exact fraction of light passing through the medium, which falls
+    /// off exponentially with the distance travelled inside it.
+    fn transmittance(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> f64 {
+        let Some((t_enter, t_exit)) = span_inside(self.boundary.as_ref(), r, ray_t) else {
+            return 1.0;
+        };
+        let distance_inside_boundary = (t_exit - t_enter) * r.direction().length();
+        (distance_inside_boundary / self.neg_inv_density).exp()
+    }
+}
+
+impl Display for ConstantMedium {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "medium of density {} in {}",
+            -1.0 / self.neg_inv_density,
+            self.boundary
+        )
+    }
+}
+
+impl ConstantMedium {
+    /// Builds a new ConstantMedium filling boundary, scattering with an Isotropic
+    /// phase function whose albedo varies with a Texture.
+    pub fn build(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+        Self::from(boundary, density, Arc::new(Isotropic::from_texture(tex)))
+    }
+
+    /// Constructs a new ConstantMedium filling boundary, scattering with the given
+    /// phase function material.
+    pub fn from(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+/// Returns the interval of t over which a Ray is inside a convex boundary, clipped to
+/// ray_t, or None if it is never inside within ray_t. The ray's line is followed
+/// behind its origin too, so that rays starting inside the boundary are handled.
+pub(crate) fn span_inside(
+    boundary: &dyn Hittable,
+    r: &Ray,
+    ray_t: RangeInclusive<f64>,
+) -> Option<(f64, f64)> {
+    let mut rec1 = HitRecord::new();
+    let mut rec2 = HitRecord::new();
+    if !boundary.hit(r, f64::NEG_INFINITY..=f64::INFINITY, &mut rec1) {
+        return None;
+    }
+    if !boundary.hit(r, rec1.t + 0.0001..=f64::INFINITY, &mut rec2) {
+        return None;
+    }
+
+    let t_enter = rec1.t.max(*ray_t.start()).max(0.0);
+    let t_exit = rec2.t.min(*ray_t.end());
+    (t_enter < t_exit).then_some((t_enter, t_exit))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColor;
+    use crate::transform::{Instance, Transform};
+    use crate::vec3::Point3;
+
+    #[test]
+    fn test_hit() {
+        let boundary = Arc::new(Sphere::build(
+            Point3::new(),
+            1.0,
+            Arc::new(Isotropic::build(1.0, 1.0, 1.0)),
+        ));
+        let white = Arc::new(SolidColor::build(1.0, 1.0, 1.0));
+        let mut rec = HitRecord::new();
+
+        // So dense that every ray scatters right where it enters.
+        let fog = ConstantMedium::build(boundary.clone(), 1e12, white.clone());
+        let outside = Ray::build(Point3::build(0.0, 0.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(fog.hit(&outside, 0.001..=f64::INFINITY, &mut rec));
+        assert!((rec.t - 4.0).abs() < 1e-6);
+
+        // A ray starting inside scatters near its origin, not behind it.
+        let inside = Ray::build(Point3::new(), Vec3::build(0.0, 0.0, -1.0));
+        assert!(fog.hit(&inside, 0.001..=f64::INFINITY, &mut rec));
+        assert!((0.001..0.01).contains(&rec.t));
+
+        // So thin that no ray scatters.
+        let haze = ConstantMedium::build(boundary, 1e-12, white);
+        assert!(!haze.hit(&outside, 0.001..=f64::INFINITY, &mut rec));
+        let past = Ray::build(Point3::build(0.0, 2.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(!haze.hit(&past, 0.001..=f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn test_shadow_rays() {
+        let white = Arc::new(Isotropic::build(1.0, 1.0, 1.0));
+        let boundary = Arc::new(Sphere::build(Point3::new(), 1.0, white.clone()));
+        let fog = ConstantMedium::from(boundary, 0.5, white.clone());
+        let wall = Sphere::build(Point3::build(0.0, 0.0, -10.0), 1.0, white);
+        let mut world = HittableList::new();
+        world.add(fog);
+        world.add(wall);
+
+        // Shadow rays pass through the medium to the wall behind it, losing e^-1 of
+        // their light across its diameter.
+        let r = Ray::build(Point3::build(0.0, 0.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::new();
+        assert!(world.shadow_hit(&r, 0.001..=f64::INFINITY, &mut rec));
+        assert!((rec.t - 14.0).abs() < 1e-9);
+        let transmittance = world.transmittance(&r, 0.001..=rec.t);
+        assert!((transmittance - (-1.0_f64).exp()).abs() < 1e-9);
+        assert_eq!(world.transmittance(&r, 0.001..=3.0), 1.0);
+
+        let moved = Instance::build(
+            Arc::new(world),
+            Transform::translate(&Vec3::build(0.0, 5.0, 0.0)),
+        );
+        let above = Ray::build(Point3::build(0.0, 5.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        let transmittance = moved.unwrap().transmittance(&above, 0.001..=f64::INFINITY);
+        assert!((transmittance - (-1.0_f64).exp()).abs() < 1e-9);
+    }
+}
@@ -1,22 +1,63 @@
+use crate::aabb::Aabb;
 use crate::material::{Lambertian, Material};
 use crate::ray::Ray;
-use crate::vec3::{dot, Color, Point3, Vec3};
+use crate::vec3::{dot, Point3, Vec3};
 
 use std::fmt::Display;
 use std::ops::RangeInclusive;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// Contains data related to a Ray hitting an object.
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
     pub t: f64,
+    pub u: f64, // Surface coordinates of the hit point
+    pub v: f64, //
     pub front_face: bool,
+    pub object_id: u32, // ID of the Tagged object hit, or 0 if it has none
 }
 
-pub trait Hittable: Display {
+/// Describes a Hittable wrapped with an ID, which it writes into the HitRecord of each
+/// of its hits so that renders can tell objects apart. Hits on untagged objects get
+/// ID 0.
+pub struct Tagged {
+    object: Arc<dyn Hittable>,
+    id: u32,
+}
+
+/// Hittables are shared between render threads, so they must be Send + Sync.
+pub trait Hittable: Display + Send + Sync {
     fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool;
+
+    /// Returns the axis-aligned box enclosing the whole object.
+    fn bounding_box(&self) -> Aabb;
+
+    /// Returns the probability density, over solid angle, of `random` choosing the
+    /// given direction from origin. Objects that cannot be sampled as lights return 0.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Returns a random direction from origin toward the object.
+    fn random(&self, _origin: &Point3) -> Vec3 {
+        Vec3::build(1.0, 0.0, 0.0)
+    }
+
+    /// Determines if a shadow ray hits a surface of the object, as `hit` does. Shadow
+    /// rays pass through participating media, which return false and account for the
+    /// light they absorb by `transmittance` instead.
+    fn shadow_hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        self.hit(r, ray_t, rec)
+    }
+
+    /// Returns the fraction of light passing through the participating media of the
+    /// object along a Ray over the interval ray_t, or an unbiased estimate of it.
+    /// Surfaces let all light through, since shadow rays stop at them.
+    fn transmittance(&self, _r: &Ray, _ray_t: RangeInclusive<f64>) -> f64 {
+        1.0
+    }
 }
 
 impl HitRecord {
@@ -42,11 +83,59 @@ impl Default for HitRecord {
         Self {
             p: Point3::new(),
             normal: Vec3::new(),
-            mat: Rc::new(Lambertian {
-                albedo: Color::new(),
-            }),
+            mat: Arc::new(Lambertian::build(0.0, 0.0, 0.0)),
             t: 0.,
+            u: 0.,
+            v: 0.,
             front_face: true,
+            object_id: 0,
+        }
+    }
+}
+
+impl Hittable for Tagged {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        if !self.object.hit(r, ray_t, rec) {
+            return false;
+        }
+        rec.object_id = self.id;
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.object.random(origin)
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        if !self.object.shadow_hit(r, ray_t, rec) {
+            return false;
         }
+        rec.object_id = self.id;
+        true
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> f64 {
+        self.object.transmittance(r, ray_t)
+    }
+}
+
+impl Display for Tagged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} {}", self.id, self.object)
+    }
+}
+
+impl Tagged {
+    /// Builds a new Tagged wrapping object with the given ID.
+    pub fn build(object: Arc<dyn Hittable>, id: u32) -> Self {
+        Self { object, id }
     }
 }
@@ -1,34 +1,68 @@
+use crate::aabb::Aabb;
 use crate::hittable::*;
+use crate::random_f64;
 use crate::ray::*;
+use crate::vec3::{Point3, Vec3};
 use std::fmt::Display;
 use std::ops::RangeInclusive;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Default)]
 pub struct HittableList {
-    pub objects: Vec<Rc<dyn Hittable>>,
+    pub objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl Hittable for HittableList {
     /// Determines if a Ray hits anything when cast into the world (HittableList).
     fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::new();
-        let mut hit_anything = false;
-        let mut closest_so_far = *ray_t.end();
+        self.closest_hit(r, ray_t, rec, |object, r, ray_t, rec| {
+            object.hit(r, ray_t, rec)
+        })
+    }
 
-        for object in &self.objects {
-            let interval = *ray_t.start()..=closest_so_far;
-            if object.hit(r, interval, &mut temp_rec) {
-                hit_anything = true;
-                closest_so_far = temp_rec.t;
-                rec.p = temp_rec.p;
-                rec.normal = temp_rec.normal;
-                rec.mat = temp_rec.mat.clone();
-                rec.t = temp_rec.t;
-                rec.front_face = temp_rec.front_face;
-            }
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bbox, object| {
+            bbox.surrounding(&object.bounding_box())
+        })
+    }
+
+    /// Returns the density of `random` choosing the direction, averaged over the
+    /// objects since each is equally likely to be the one sampled.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
         }
-        hit_anything
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    /// Returns a random direction from origin toward one of the objects, chosen with
+    /// equal probability.
+    fn random(&self, origin: &Point3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::build(1.0, 0.0, 0.0);
+        }
+        let n = self.objects.len();
+        let i = ((random_f64() * n as f64) as usize).min(n - 1);
+        self.objects[i].random(origin)
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        self.closest_hit(r, ray_t, rec, |object, r, ray_t, rec| {
+            object.shadow_hit(r, ray_t, rec)
+        })
+    }
+
+    /// Returns the product of the objects' transmittances.
+    fn transmittance(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> f64 {
+        self.objects
+            .iter()
+            .map(|object| object.transmittance(r, ray_t.clone()))
+            .product()
     }
 }
 
@@ -47,7 +81,37 @@ impl Display for HittableList {
 impl HittableList {
     /// Adds a Hittable object to the HittableList.
     pub fn add(&mut self, object: impl Hittable + 'static) {
-        self.objects.push(Rc::new(object));
+        self.objects.push(Arc::new(object));
+    }
+
+    /// Finds the closest of the objects' hits, as found by the given hit method.
+    fn closest_hit(
+        &self,
+        r: &Ray,
+        ray_t: RangeInclusive<f64>,
+        rec: &mut HitRecord,
+        hit: impl Fn(&dyn Hittable, &Ray, RangeInclusive<f64>, &mut HitRecord) -> bool,
+    ) -> bool {
+        let mut temp_rec = HitRecord::new();
+        let mut hit_anything = false;
+        let mut closest_so_far = *ray_t.end();
+
+        for object in &self.objects {
+            let interval = *ray_t.start()..=closest_so_far;
+            if hit(object.as_ref(), r, interval, &mut temp_rec) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                rec.p = temp_rec.p;
+                rec.normal = temp_rec.normal;
+                rec.mat = temp_rec.mat.clone();
+                rec.t = temp_rec.t;
+                rec.u = temp_rec.u;
+                rec.v = temp_rec.v;
+                rec.front_face = temp_rec.front_face;
+                rec.object_id = temp_rec.object_id;
+            }
+        }
+        hit_anything
     }
 
     /// Constructs a default-initialie HittableList.
@@ -0,0 +1,39 @@
+use crate::vec3::Color;
+
+/// Describes a rendered image held in memory. Each pixel stores its linear
+/// (not gamma-corrected) color, averaged over the samples taken for it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>, // Linear RGB, row-major from the top-left pixel
+    pub samples: Vec<u32>,  // Number of samples averaged into each pixel
+}
+
+impl Image {
+    /// Builds a new black Image of the given dimensions, with no samples taken.
+    pub fn build(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::new(); width * height],
+            samples: vec![0; width * height],
+        }
+    }
+
+    /// Returns the linear color of the pixel at column i, row j.
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        self.pixels[j * self.width + i]
+    }
+
+    /// Returns the number of samples averaged into the pixel at column i, row j.
+    pub fn sample_count(&self, i: usize, j: usize) -> u32 {
+        self.samples[j * self.width + i]
+    }
+
+    /// Sets the linear color and sample count of the pixel at column i, row j.
+    pub fn set_pixel(&mut self, i: usize, j: usize, color: Color, samples: u32) {
+        self.pixels[j * self.width + i] = color;
+        self.samples[j * self.width + i] = samples;
+    }
+}
@@ -0,0 +1,1087 @@
+//! Readers and writers for Images in common file formats.
+//!
+//! Every format is encoded by hand: 8-bit PNG and binary (P6) or ASCII (P3) PPM are
+//! gamma-corrected for display, while Radiance HDR keeps the full linear range.
+
+use crate::image::Image;
+use crate::vec3::{gamma_to_linear, linear_to_gamma, write_color, Color};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// File extensions `read_image` and `write_image` know how to decode and encode.
+pub const EXTENSIONS: [&str; 3] = ["png", "ppm", "hdr"];
+
+/// Returns true if `write_image` can encode the format implied by the path's extension.
+pub fn is_supported(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Writes an Image to a file, choosing the format from the file extension:
+/// `.png`, `.ppm` (binary P6) or `.hdr` (Radiance RGBE).
+pub fn write_image(image: &Image, path: &Path) -> io::Result<()> {
+    if !is_supported(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format: {}", path.display()),
+        ));
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    match extension(path).as_deref() {
+        Some("png") => write_png(image, &mut out)?,
+        Some("ppm") => write_p6(image, &mut out)?,
+        _ => write_hdr(image, &mut out)?,
+    }
+    out.flush()
+}
+
+/// Reads an Image from a file, choosing the format from the file extension:
+/// `.png`, `.ppm` (P3 or P6) or `.hdr`. 8-bit formats are converted to linear color.
+pub fn read_image(path: &Path) -> io::Result<Image> {
+    let mut input = BufReader::new(File::open(path)?);
+    match extension(path).as_deref() {
+        Some("png") => read_png(&mut input),
+        Some("ppm") => read_ppm(&mut input),
+        Some("hdr") => read_hdr(&mut input),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format: {}", path.display()),
+        )),
+    }
+}
+
+/// Returns the lowercase extension of a path, if it has one.
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+/// Writes an Image as a gamma-corrected ASCII (P3) PPM file.
+pub fn write_p3(image: &Image, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "P3\n{} {} \n255", image.width, image.height)?;
+    for pixel_color in &image.pixels {
+        writeln!(out, "{}", write_color(pixel_color, 1))?;
+    }
+    Ok(())
+}
+
+/// Writes an Image as a gamma-corrected binary (P6) PPM file.
+pub fn write_p6(image: &Image, out: &mut impl Write) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", image.width, image.height)?;
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(to_rgb8).collect();
+    out.write_all(&bytes)
+}
+
+/// Writes an Image as a gamma-corrected 8-bit RGB PNG file.
+pub fn write_png(image: &Image, out: &mut impl Write) -> io::Result<()> {
+    const BPP: usize = 3; // Bytes per pixel
+
+    // Filter each scanline with whichever PNG filter makes it smallest, judged by
+    // the usual heuristic of the minimum sum of absolute (signed) byte values.
+    let stride = image.width * BPP;
+    let mut raw = Vec::with_capacity((stride + 1) * image.height);
+    let mut prior = vec![0u8; stride];
+    let mut filtered = vec![0u8; stride];
+    let mut best = vec![0u8; stride];
+    for row in image.pixels.chunks(image.width.max(1)) {
+        let line: Vec<u8> = row.iter().flat_map(to_rgb8).collect();
+
+        let mut best_type = 0;
+        let mut best_score = u64::MAX;
+        for filter_type in 0..5u8 {
+            png_filter(filter_type, &line, &prior, BPP, &mut filtered);
+            let score = filtered
+                .iter()
+                .map(|&b| (b as i8).unsigned_abs() as u64)
+                .sum();
+            if score < best_score {
+                best_score = score;
+                best_type = filter_type;
+                best.copy_from_slice(&filtered);
+            }
+        }
+
+        raw.push(best_type);
+        raw.extend_from_slice(&best);
+        prior = line;
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(image.width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(image.height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit depth, RGB, deflate, no interlace
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_png_chunk(out, b"IHDR", &ihdr)?;
+    write_png_chunk(out, b"IDAT", &zlib_compress(&raw))?;
+    write_png_chunk(out, b"IEND", &[])
+}
+
+/// Writes an Image as a Radiance RGBE (.hdr) file, preserving linear color values
+/// above 1.0.
+pub fn write_hdr(image: &Image, out: &mut impl Write) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height, image.width
+    )?;
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(to_rgbe).collect();
+    out.write_all(&bytes)
+}
+
+/// Reads a Radiance RGBE (.hdr) file, with either flat or run-length encoded
+/// scanlines, into an Image of linear colors.
+pub fn read_hdr(input: &mut impl BufRead) -> io::Result<Image> {
+    // The header is a list of variables ended by a blank line, then the resolution.
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("not a Radiance HDR file"));
+    }
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Err(invalid_data("unexpected end of HDR header"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data(&format!(
+                    "unsupported HDR format '{}'",
+                    format
+                )));
+            }
+        }
+    }
+
+    line.clear();
+    input.read_line(&mut line)?;
+    let (height, width): (Option<usize>, Option<usize>) =
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", h, "+X", w] => (h.parse().ok(), w.parse().ok()),
+            _ => (None, None),
+        };
+    let (Some(height), Some(width)) = (height, width) else {
+        return Err(invalid_data("unsupported HDR resolution or orientation"));
+    };
+
+    // Read the pixel data first, so that a resolution the file is too short to hold
+    // is rejected before any pixels are allocated. A scanline takes at least four
+    // bytes per pixel, or, run-length encoded, its start and a run per 127 pixels of
+    // each channel.
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    let scanline_size = width.checked_mul(4);
+    let least_size = match hdr_encodable(width) {
+        true => Some(4 + 8 * width.div_ceil(127)),
+        false => scanline_size,
+    };
+    let backed = width.checked_mul(height).is_some()
+        && least_size
+            .and_then(|size| size.checked_mul(height))
+            .is_some_and(|size| size <= data.len());
+    let (Some(scanline_size), true) = (scanline_size, backed) else {
+        return Err(invalid_data("HDR file is too short for its resolution"));
+    };
+
+    let mut input = data.as_slice();
+    let mut image = Image::build(width, height);
+    let mut scanline = vec![0u8; scanline_size];
+    for j in 0..height {
+        read_hdr_scanline(&mut input, &mut scanline)?;
+        for (i, rgbe) in scanline.chunks(4).enumerate() {
+            image.set_pixel(i, j, from_rgbe(rgbe), 1);
+        }
+    }
+    Ok(image)
+}
+
+/// Reads one scanline of RGBE bytes, decoding it if it is run-length encoded.
+fn read_hdr_scanline(input: &mut impl BufRead, scanline: &mut [u8]) -> io::Result<()> {
+    let width = scanline.len() / 4;
+    let mut start = [0u8; 4];
+    input.read_exact(&mut start)?;
+
+    let encoded = hdr_encodable(width)
+        && start[0] == 2
+        && start[1] == 2
+        && (start[2] as usize) << 8 | start[3] as usize == width;
+    if !encoded {
+        scanline[..4].copy_from_slice(&start);
+        return input.read_exact(&mut scanline[4..]);
+    }
+
+    // Each channel is stored separately as runs of a repeated byte or literal bytes.
+    for channel in 0..4 {
+        let mut i = 0;
+        while i < width {
+            let mut count = [0u8; 1];
+            input.read_exact(&mut count)?;
+            // Counts above 128 are runs of count - 128; the rest, up to 128, are
+            // literal dumps.
+            let (run, count) = match count[0] {
+                c @ 129.. => (true, (c - 128) as usize),
+                c => (false, c as usize),
+            };
+            if count == 0 || i + count > width {
+                return Err(invalid_data("bad HDR scanline run length"));
+            }
+            if run {
+                let mut value = [0u8; 1];
+                input.read_exact(&mut value)?;
+                for k in i..i + count {
+                    scanline[k * 4 + channel] = value[0];
+                }
+            } else {
+                let mut values = vec![0u8; count];
+                input.read_exact(&mut values)?;
+                for (k, value) in values.into_iter().enumerate() {
+                    scanline[(i + k) * 4 + channel] = value;
+                }
+            }
+            i += count;
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether scanlines of the given width can be run-length encoded.
+fn hdr_encodable(width: usize) -> bool {
+    (8..0x8000).contains(&width)
+}
+
+/// Reads an ASCII (P3) or binary (P6) PPM file into an Image of linear colors.
+pub fn read_ppm(input: &mut impl BufRead) -> io::Result<Image> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+
+    // The header is whitespace-separated tokens, possibly with # comments between.
+    let mut pos = 0;
+    let mut token = || {
+        loop {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        (
+            String::from_utf8_lossy(&bytes[start..pos]).into_owned(),
+            pos,
+        )
+    };
+    let number = |(text, _): (String, usize)| -> io::Result<usize> {
+        text.parse()
+            .map_err(|_| invalid_data(&format!("bad PPM header value '{}'", text)))
+    };
+
+    let (magic, _) = token();
+    let width = number(token())?;
+    let height = number(token())?;
+    let (max_text, data_start) = token();
+    let max_value = number((max_text, data_start))?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data("bad PPM maximum value"));
+    }
+
+    let samples: Vec<usize> = match magic.as_str() {
+        "P3" => String::from_utf8_lossy(&bytes[data_start..])
+            .split_whitespace()
+            .map(|t| number((t.to_string(), 0)))
+            .collect::<io::Result<_>>()?,
+        "P6" => {
+            // A single whitespace byte separates the header from the binary data.
+            let data = bytes.get(data_start + 1..).unwrap_or(&[]);
+            if max_value < 256 {
+                data.iter().map(|&b| b as usize).collect()
+            } else {
+                data.chunks(2)
+                    .map(|b| (b[0] as usize) << 8 | *b.get(1).unwrap_or(&0) as usize)
+                    .collect()
+            }
+        }
+        _ => return Err(invalid_data("not a PPM file")),
+    };
+    let pixel_count = width
+        .checked_mul(height)
+        .filter(|n| n.checked_mul(3).is_some_and(|size| size <= samples.len()))
+        .ok_or_else(|| invalid_data("PPM file is truncated"))?;
+
+    let mut image = Image::build(width, height);
+    for (index, rgb) in samples.chunks(3).take(pixel_count).enumerate() {
+        let channel = |c: usize| gamma_to_linear(c as f64 / max_value as f64);
+        let color = Color::build(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]));
+        image.set_pixel(index % width, index / width, color, 1);
+    }
+    Ok(image)
+}
+
+/// Reads a non-interlaced PNG file of any color type, at 8 or 16 bits per channel,
+/// into an Image of linear colors. Alpha is ignored.
+pub fn read_png(input: &mut impl BufRead) -> io::Result<Image> {
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Err(invalid_data("not a PNG file"));
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = Vec::new();
+    let mut pos = 8;
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        let chunk_type = &bytes[pos + 4..pos + 8];
+        let data = bytes
+            .get(pos + 8..pos + 8 + length)
+            .ok_or_else(|| invalid_data("PNG chunk is truncated"))?;
+        match chunk_type {
+            b"IHDR" if length >= 13 => header = Some(data),
+            b"PLTE" => palette = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += length + 12;
+    }
+
+    let header = header.ok_or_else(|| invalid_data("PNG file has no header"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let (bit_depth, color_type, interlace) = (header[8] as usize, header[9], header[12]);
+    let channels = match color_type {
+        0 => 1, // Grayscale
+        2 => 3, // RGB
+        3 => 1, // Palette index
+        4 => 2, // Grayscale and alpha
+        6 => 4, // RGBA
+        _ => return Err(invalid_data("bad PNG color type")),
+    };
+    if !(bit_depth == 8 || bit_depth == 16 && color_type != 3) || interlace != 0 {
+        return Err(invalid_data("unsupported PNG bit depth or interlacing"));
+    }
+
+    // Undo each scanline's filter, using the already unfiltered scanline above.
+    let raw = zlib_decompress(&compressed)?;
+    let bpp = channels * bit_depth / 8;
+    let stride = width
+        .checked_mul(bpp)
+        .filter(|stride| {
+            (stride + 1)
+                .checked_mul(height)
+                .is_some_and(|size| size <= raw.len())
+        })
+        .ok_or_else(|| invalid_data("PNG image data is truncated"))?;
+    let mut prior = vec![0u8; stride];
+    let mut line = vec![0u8; stride];
+    let mut image = Image::build(width, height);
+    for j in 0..height {
+        let row = &raw[j * (stride + 1)..(j + 1) * (stride + 1)];
+        png_unfilter(row[0], &row[1..], &prior, bpp, &mut line)?;
+
+        for i in 0..width {
+            let sample = |c: usize| match bit_depth {
+                8 => line[i * bpp + c] as f64 / 255.0,
+                _ => {
+                    u16::from_be_bytes([line[i * bpp + 2 * c], line[i * bpp + 2 * c + 1]]) as f64
+                        / 65535.0
+                }
+            };
+            let (r, g, b) = match color_type {
+                0 | 4 => (sample(0), sample(0), sample(0)),
+                3 => {
+                    let index = line[i] as usize * 3;
+                    let rgb = palette
+                        .get(index..index + 3)
+                        .ok_or_else(|| invalid_data("PNG palette index out of range"))?;
+                    let channel = |c: u8| c as f64 / 255.0;
+                    (channel(rgb[0]), channel(rgb[1]), channel(rgb[2]))
+                }
+                _ => (sample(0), sample(1), sample(2)),
+            };
+            let color = Color::build(gamma_to_linear(r), gamma_to_linear(g), gamma_to_linear(b));
+            image.set_pixel(i, j, color, 1);
+        }
+        std::mem::swap(&mut prior, &mut line);
+    }
+    Ok(image)
+}
+
+/// Returns an io::Error for a file that is malformed or uses unsupported features.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Pixel encodings
+
+/// Converts Radiance's shared-exponent RGBE encoding to a linear color.
+fn from_rgbe(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new();
+    }
+    let scale = 2.0_f64.powi(rgbe[3] as i32 - (128 + 8));
+    Color::build(
+        rgbe[0] as f64 * scale,
+        rgbe[1] as f64 * scale,
+        rgbe[2] as f64 * scale,
+    )
+}
+
+/// Converts a linear color to gamma-corrected 8-bit RGB, as `write_color` does.
+fn to_rgb8(pixel_color: &Color) -> [u8; 3] {
+    let intensity = 0.000..0.999;
+    let channel = |c: f64| {
+        let gamma = linear_to_gamma(c);
+        (256.0 * gamma.clamp(intensity.start, intensity.end)) as u8
+    };
+    [
+        channel(pixel_color.x()),
+        channel(pixel_color.y()),
+        channel(pixel_color.z()),
+    ]
+}
+
+/// Converts a linear color to Radiance's shared-exponent RGBE encoding.
+fn to_rgbe(pixel_color: &Color) -> [u8; 4] {
+    let [r, g, b] = pixel_color.e.map(|c| c.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // v = mantissa * 2^exponent, with the mantissa in [0.5, 1).
+    let exponent = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2.0_f64.powi(exponent);
+    [
+        (r * scale).min(255.0) as u8,
+        (g * scale).min(255.0) as u8,
+        (b * scale).min(255.0) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// PNG
+
+/// Applies PNG filter filter_type to a scanline, given the unfiltered scanline above.
+fn png_filter(filter_type: u8, line: &[u8], prior: &[u8], bpp: usize, out: &mut [u8]) {
+    for i in 0..line.len() {
+        let a = if i >= bpp { line[i - bpp] } else { 0 }; // Left
+        let b = prior[i]; // Up
+        let c = if i >= bpp { prior[i - bpp] } else { 0 }; // Up-left
+        out[i] = line[i].wrapping_sub(match filter_type {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth_predictor(a, b, c),
+        });
+    }
+}
+
+/// Reverses PNG filter filter_type on a scanline, given the unfiltered scanline above.
+fn png_unfilter(
+    filter_type: u8,
+    filtered: &[u8],
+    prior: &[u8],
+    bpp: usize,
+    out: &mut [u8],
+) -> io::Result<()> {
+    if filter_type > 4 {
+        return Err(invalid_data("bad PNG filter type"));
+    }
+    for i in 0..filtered.len() {
+        let a = if i >= bpp { out[i - bpp] } else { 0 }; // Left
+        let b = prior[i]; // Up
+        let c = if i >= bpp { prior[i - bpp] } else { 0 }; // Up-left
+        out[i] = filtered[i].wrapping_add(match filter_type {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth_predictor(a, b, c),
+        });
+    }
+    Ok(())
+}
+
+/// Returns whichever of the left, up, or up-left bytes is nearest to a + b - c.
+fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Writes a PNG chunk: length, type, data and the CRC of the type and data.
+fn write_png_chunk(out: &mut impl Write, chunk_type: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(chunk_type)?;
+    out.write_all(data)?;
+    let crc = crc32(&[chunk_type.as_slice(), data].concat());
+    out.write_all(&crc.to_be_bytes())
+}
+
+/// Returns the CRC-32 (ISO 3309) checksum used by PNG chunks.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Returns the Adler-32 checksum used by zlib streams.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Deflate
+
+/// Base match lengths for length symbols 257..=285, and their extra bit counts.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base match distances for distance symbols 0..=29, and their extra bit counts.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64; // Candidate matches examined per position
+const HASH_BITS: usize = 15;
+
+/// Accumulates a deflate bit stream, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            bit_buf: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Appends the low `count` bits of value.
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bit_buf |= value << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buf as u8);
+            self.bit_buf >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Appends a Huffman code, which deflate stores most significant bit first.
+    fn write_code(&mut self, code: u32, len: u32) {
+        let reversed = code.reverse_bits() >> (32 - len);
+        self.write_bits(reversed, len);
+    }
+
+    /// Appends the fixed Huffman code for a literal/length symbol.
+    fn write_literal_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    /// Appends a back-reference of the given length and distance.
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let li = LENGTH_BASE
+            .iter()
+            .rposition(|&b| b as usize <= length)
+            .unwrap();
+        self.write_literal_symbol(257 + li as u32);
+        self.write_bits(
+            (length - LENGTH_BASE[li] as usize) as u32,
+            LENGTH_EXTRA[li] as u32,
+        );
+
+        let di = DIST_BASE
+            .iter()
+            .rposition(|&b| b as usize <= distance)
+            .unwrap();
+        self.write_code(di as u32, 5);
+        self.write_bits(
+            (distance - DIST_BASE[di] as usize) as u32,
+            DIST_EXTRA[di] as u32,
+        );
+    }
+
+    /// Flushes any partial byte and returns the stream.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bit_buf as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses data into a zlib stream holding a single fixed-Huffman deflate block,
+/// finding repeated strings with hash chains over a 32 KiB window.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let hash = |i: usize| {
+        let v = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+        (v.wrapping_mul(2654435761) >> 16) & ((1 << HASH_BITS) - 1)
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i % WINDOW_SIZE] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut bits = BitWriter::new();
+    bits.write_bits(1, 1); // BFINAL: this is the last block
+    bits.write_bits(1, 2); // BTYPE: fixed Huffman codes
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let len = (0..max_len)
+                    .take_while(|&k| data[candidate + k] == data[i + k])
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW_SIZE];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            bits.write_match(best_len, best_dist);
+            for k in i..i + best_len {
+                insert(k, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            bits.write_literal_symbol(data[i] as u32);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    bits.write_literal_symbol(256); // End of block
+
+    let mut stream = vec![0x78, 0x01]; // Deflate with a 32 KiB window, no preset dictionary
+    stream.extend(bits.finish());
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Inflate
+
+/// Order in which code length code lengths are stored in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads a deflate bit stream, least significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize, // Index of the next unread byte
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl BitReader<'_> {
+    /// Reads `count` bits, returning them as the low bits of the result.
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.bit_count < count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid_data("deflate stream is truncated"))?;
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+            self.pos += 1;
+        }
+        let value = self.bit_buf & ((1u64 << count) - 1) as u32;
+        self.bit_buf >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    /// Reads one symbol coded with the given Huffman code.
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        // Walk down the canonical code one bit at a time. At each length, codes
+        // first..first + count are the codes of that length, in symbol order.
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[len] as i32;
+            if code - first < count {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("bad deflate Huffman code"))
+    }
+}
+
+/// Describes a canonical Huffman code by the number of codes of each bit length and
+/// the symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the canonical Huffman code for the given code length of each symbol.
+    fn build(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|&s| lengths[s as usize] != 0)
+            .collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+        Self { counts, symbols }
+    }
+}
+
+/// Decompresses a zlib stream.
+fn zlib_decompress(stream: &[u8]) -> io::Result<Vec<u8>> {
+    if stream.len() < 2 || stream[0] & 0x0f != 8 || stream[1] & 0x20 != 0 {
+        return Err(invalid_data("unsupported zlib stream"));
+    }
+    let mut bits = BitReader {
+        data: &stream[2..],
+        pos: 0,
+        bit_buf: 0,
+        bit_count: 0,
+    };
+
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                // Stored block: skip to a byte boundary, then copy LEN raw bytes.
+                bits.bit_buf = 0;
+                bits.bit_count = 0;
+                let len = bits.bits(16)? as usize;
+                let nlen = bits.bits(16)? as usize;
+                if len != !nlen & 0xffff {
+                    return Err(invalid_data("bad deflate stored block length"));
+                }
+                let block = bits
+                    .data
+                    .get(bits.pos..bits.pos + len)
+                    .ok_or_else(|| invalid_data("deflate stream is truncated"))?;
+                out.extend_from_slice(block);
+                bits.pos += len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::build(&lengths);
+                let distances = Huffman::build(&[5; 30]);
+                inflate_block(&mut bits, &literals, &distances, &mut out)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &literals, &distances, &mut out)?;
+            }
+            _ => return Err(invalid_data("bad deflate block type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Reads the literal/length and distance codes from a dynamic block header.
+fn read_dynamic_codes(bits: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::build(&code_lengths);
+
+    // Both codes' lengths are run-length encoded together with the code length code.
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match bits.decode(&code_length_code)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid_data("bad deflate code lengths"))?;
+                (previous, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid_data("bad deflate code lengths"));
+    }
+
+    Ok((
+        Huffman::build(&lengths[..literal_count]),
+        Huffman::build(&lengths[literal_count..]),
+    ))
+}
+
+/// Decodes the literals and back-references of a Huffman-coded block.
+fn inflate_block(
+    bits: &mut BitReader,
+    literals: &Huffman,
+    distances: &Huffman,
+    out: &mut Vec<u8>,
+) -> io::Result<()> {
+    loop {
+        let symbol = bits.decode(literals)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let li = symbol - 257;
+                let length =
+                    LENGTH_BASE[li] as usize + bits.bits(LENGTH_EXTRA[li] as u32)? as usize;
+                let di = bits.decode(distances)? as usize;
+                if di >= DIST_BASE.len() {
+                    return Err(invalid_data("bad deflate distance code"));
+                }
+                let distance = DIST_BASE[di] as usize + bits.bits(DIST_EXTRA[di] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(invalid_data("deflate distance is too far back"));
+                }
+                // Copy byte by byte, since a match may overlap the bytes it produces.
+                let start = out.len() - distance;
+                for k in 0..length {
+                    out.push(out[start + k]);
+                }
+            }
+            _ => return Err(invalid_data("bad deflate literal/length code")),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_zlib_round_trip() {
+        let mut noise = Vec::new();
+        let mut x: u32 = 1;
+        for _ in 0..5000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            noise.push((x >> 16) as u8);
+        }
+        let runs: Vec<u8> = (0..100_000).map(|i| (i / 300 % 7) as u8).collect();
+        let text = b"abcabcabcabc to be or not to be, that is the question".repeat(40);
+
+        for data in [Vec::new(), vec![0; 1000], noise, runs, text] {
+            let stream = zlib_compress(&data);
+            assert_eq!(zlib_decompress(&stream).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut image = Image::build(37, 23);
+        for j in 0..23 {
+            for i in 0..37 {
+                let c = Color::build((i % 4) as f64 / 4.0, (j % 3) as f64 / 3.0, 0.5);
+                image.set_pixel(i, j, c, 1);
+            }
+        }
+
+        let mut png = Vec::new();
+        write_png(&image, &mut png).unwrap();
+        let mut ppm = Vec::new();
+        write_p6(&image, &mut ppm).unwrap();
+
+        // Decoding both gives the same linear colors, near the original ones.
+        let from_png = read_png(&mut png.as_slice()).unwrap();
+        let from_ppm = read_ppm(&mut ppm.as_slice()).unwrap();
+        assert_eq!(from_png, from_ppm);
+        for (decoded, original) in from_png.pixels.iter().zip(&image.pixels) {
+            assert!((*decoded - *original).length() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_huge_sizes() {
+        let ppm = b"P6 4294967296 4294967296 255\n\x00\x00\x00";
+        let err = read_ppm(&mut ppm.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // 2^32 - 1 scanlines of 2^32 - 1 pixels overflow the size of the image data.
+        let mut image = Image::build(1, 1);
+        image.set_pixel(0, 0, Color::build(0.5, 0.5, 0.5), 1);
+        let mut png = Vec::new();
+        write_png(&image, &mut png).unwrap();
+        let ihdr = png.windows(4).position(|w| w == b"IHDR").unwrap() + 4;
+        png[ihdr..ihdr + 8].copy_from_slice(&[0xff; 8]);
+        let err = read_png(&mut png.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_inflate_stored_block() {
+        let stream = [0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(zlib_decompress(&stream).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(to_rgbe(&Color::new()), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(&Color::build(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(from_rgbe(&[128, 64, 32, 129]), Color::build(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_hdr_round_trip() {
+        let mut image = Image::build(3, 2);
+        image.set_pixel(0, 0, Color::build(16.0, 0.5, 0.0), 1);
+        image.set_pixel(2, 1, Color::build(0.125, 0.25, 1.0), 1);
+
+        let mut bytes = Vec::new();
+        write_hdr(&image, &mut bytes).unwrap();
+        let read = read_hdr(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.pixels, image.pixels);
+    }
+
+    #[test]
+    fn test_hdr_run_length() {
+        let mut bytes = b"#?RADIANCE\n\n-Y 1 +X 8\n\x02\x02\x00\x08".to_vec();
+        bytes.extend_from_slice(&[0x88, 128]); // Red: a run of eight 128s
+        bytes.extend_from_slice(&[4, 1, 2, 3, 4, 0x84, 0]); // Green: literals, then a run
+        bytes.extend_from_slice(&[0x88, 0]); // Blue
+        bytes.extend_from_slice(&[0x88, 129]); // Exponent
+
+        let image = read_hdr(&mut bytes.as_slice()).unwrap();
+        assert_eq!(image.pixel(0, 0), Color::build(1.0, 1.0 / 128.0, 0.0));
+        assert_eq!(image.pixel(7, 0), Color::build(1.0, 0.0, 0.0));
+
+        // A literal dump can hold 128 bytes, the longest a count byte allows.
+        let mut bytes = b"#?RADIANCE\n\n-Y 1 +X 130\n\x02\x02\x00\x82".to_vec();
+        bytes.push(128);
+        bytes.extend((0..128).map(|i| i as u8));
+        bytes.extend_from_slice(&[2, 128, 129]); // Red
+        bytes.extend_from_slice(&[0x80 + 127, 0, 0x83, 0]); // Green
+        bytes.extend_from_slice(&[0x80 + 127, 0, 0x83, 0]); // Blue
+        bytes.extend_from_slice(&[0x80 + 127, 129, 0x83, 129]); // Exponent
+
+        let image = read_hdr(&mut bytes.as_slice()).unwrap();
+        assert_eq!(image.pixel(1, 0), Color::build(1.0 / 128.0, 0.0, 0.0));
+        assert_eq!(image.pixel(127, 0), Color::build(127.0 / 128.0, 0.0, 0.0));
+        assert_eq!(image.pixel(129, 0), Color::build(129.0 / 128.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_hdr_too_short() {
+        for resolution in [
+            "-Y 200000 +X 200000",
+            "-Y 2 +X 9223372036854775807",
+            "-Y 2 +X 8",
+        ] {
+            let mut bytes = format!("#?RADIANCE\n\n{}\n", resolution).into_bytes();
+            bytes.extend_from_slice(&[0, 0, 0, 0, 2, 2, 0, 8]);
+            let err = read_hdr(&mut bytes.as_slice()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use crate::camera::Camera;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Scatter, ScatterRecord};
+use crate::pdf::{CosinePdf, HittablePdf, Pdf};
+use crate::random_f64;
+use crate::ray::Ray;
+use crate::vec3::Color;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Describes a light transport algorithm: how the light arriving along a camera ray
+/// is found.
+pub trait Integrator: Display + Send + Sync {
+    /// Returns the light arriving at the camera along r from the world. The lights
+    /// are the emitters in the world worth sampling directly. Settings such as the
+    /// bounce limit, background and fog are taken from the camera.
+    fn ray_color(
+        &self,
+        r: &Ray,
+        camera: &Camera,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+    ) -> Color;
+}
+
+/// Describes an ambient occlusion integrator, which shades each surface by how
+/// open it is: the fraction of rays scattered from it, with a cosine weighting,
+/// that travel the given distance without meeting anything. Rays that miss the
+/// world see white.
+pub struct AmbientOcclusionIntegrator {
+    pub distance: f64,
+}
+
+/// Describes a path tracer that relies on scattered rays finding the lights by
+/// chance, ignoring the lights it is given.
+pub struct NaivePathIntegrator;
+
+/// Describes a path tracer that samples the lights directly at each diffuse hit,
+/// combining the shadow rays with scattered rays by multiple importance sampling.
+pub struct PathIntegrator;
+
+/// Describes a Whitted-style integrator: emitters and the background are seen
+/// directly and through mirrors and glass, and diffuse surfaces are lit only by the
+/// lights, with no light bouncing between them.
+pub struct WhittedIntegrator;
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        _camera: &Camera,
+        world: &dyn Hittable,
+        _lights: &dyn Hittable,
+    ) -> Color {
+        let white = Color::build(1.0, 1.0, 1.0);
+        let mut rec = HitRecord::new();
+        if !world.hit(r, 0.001..=f64::INFINITY, &mut rec) {
+            return white;
+        }
+
+        let direction = CosinePdf::build(&rec.normal).generate();
+        let probe = Ray::build_timed(rec.p, direction, r.time());
+        let mut probe_rec = HitRecord::new();
+        if world.hit(&probe, 0.001..=self.distance, &mut probe_rec) {
+            Color::new()
+        } else {
+            white
+        }
+    }
+}
+
+impl Integrator for NaivePathIntegrator {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        camera: &Camera,
+        world: &dyn Hittable,
+        _lights: &dyn Hittable,
+    ) -> Color {
+        trace_path(r, camera, world, None)
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        camera: &Camera,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+    ) -> Color {
+        trace_path(r, camera, world, Some(lights))
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        camera: &Camera,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+    ) -> Color {
+        let mut color = Color::new();
+        let mut throughput = Color::build(1.0, 1.0, 1.0);
+        let mut r = *r;
+
+        for _ in 0..camera.max_depth {
+            let mut rec = HitRecord::new();
+            let hit = world.hit(&r, 0.001..=f64::INFINITY, &mut rec);
+            apply_fog(
+                camera,
+                &r,
+                hit.then_some(rec.t),
+                &mut color,
+                &mut throughput,
+            );
+            if !hit {
+                color += throughput * camera.background.value(&r.direction());
+                break;
+            }
+
+            color += throughput * rec.mat.emitted(&r, &rec);
+            match rec.mat.scatter(&r, &rec) {
+                None => break,
+                Some(ScatterRecord {
+                    attenuation,
+                    scatter: Scatter::Specular(scattered),
+                }) => {
+                    throughput = throughput * attenuation;
+                    r = scattered;
+                }
+                Some(ScatterRecord {
+                    scatter: Scatter::Pdf(_),
+                    ..
+                }) => {
+                    color += throughput * sample_lights(&r, &rec, None, camera, world, lights);
+                    break;
+                }
+            }
+        }
+
+        color
+    }
+}
+
+impl Display for AmbientOcclusionIntegrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ambient occlusion within {}", self.distance)
+    }
+}
+
+impl Display for NaivePathIntegrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "naive path tracer")
+    }
+}
+
+impl Display for PathIntegrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "path tracer")
+    }
+}
+
+impl Display for WhittedIntegrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "whitted")
+    }
+}
+
+impl AmbientOcclusionIntegrator {
+    /// Builds a new AmbientOcclusionIntegrator, counting a surface as occluded by
+    /// anything within distance of it.
+    pub fn build(distance: f64) -> Self {
+        Self { distance }
+    }
+}
+
+/// Names of the integrators, as accepted by `named`.
+pub const INTEGRATOR_NAMES: [&str; 4] = ["path", "naive", "ao", "whitted"];
+
+/// Returns the integrator with the given name, with default settings: path (the
+/// default), naive, ao or whitted.
+pub fn named(name: &str) -> Option<Arc<dyn Integrator>> {
+    match name {
+        "path" => Some(Arc::new(PathIntegrator)),
+        "naive" => Some(Arc::new(NaivePathIntegrator)),
+        "ao" => Some(Arc::new(AmbientOcclusionIntegrator::build(1.0))),
+        "whitted" => Some(Arc::new(WhittedIntegrator)),
+        _ => None,
+    }
+}
+
+/// Returns the light arriving along r by path tracing.
+/// The path is followed one bounce at a time, tracking its throughput: the fraction
+/// of the light found further along that makes it back to the camera. After the
+/// camera's roulette_depth bounces, each bounce may end the path at random, with the
+/// odds of going on set by the throughput; surviving paths have their throughput
+/// raised to make up for those ended, which keeps the image unbiased.
+///
+/// With lights to sample, light is gathered twice at each hit on a material that
+/// scatters over a spread of directions: by a shadow ray toward a point sampled on
+/// the lights, and by the scattered ray if it happens to reach a light. The two are
+/// weighted by multiple importance sampling, so scatter_pdf carries the density with
+/// which the current ray was scattered, or None for the camera ray and specular rays,
+/// which the lights could not have been sampled for.
+fn trace_path(
+    r: &Ray,
+    camera: &Camera,
+    world: &dyn Hittable,
+    lights: Option<&dyn Hittable>,
+) -> Color {
+    let mut color = Color::new();
+    let mut throughput = Color::build(1.0, 1.0, 1.0);
+    let mut r = *r;
+    let mut scatter_pdf: Option<f64> = None;
+
+    // If we've exceeded the ray bounce limit, no more light is gathered.
+    for depth in 0..camera.max_depth {
+        let mut rec = HitRecord::new();
+        let hit = world.hit(&r, 0.001..=f64::INFINITY, &mut rec);
+        apply_fog(
+            camera,
+            &r,
+            hit.then_some(rec.t),
+            &mut color,
+            &mut throughput,
+        );
+        if !hit {
+            color += throughput * camera.background.value(&r.direction());
+            break;
+        }
+
+        let mut color_from_emission = rec.mat.emitted(&r, &rec);
+        if let (Some(pdf), Some(lights)) = (scatter_pdf, lights) {
+            let light_pdf = lights.pdf_value(&r.orig, &r.direction());
+            color_from_emission *= power_heuristic(pdf, light_pdf);
+        }
+        color += throughput * color_from_emission;
+
+        match rec.mat.scatter(&r, &rec) {
+            None => break,
+            Some(ScatterRecord {
+                attenuation,
+                scatter: Scatter::Specular(scattered),
+            }) => {
+                throughput = throughput * attenuation;
+                r = scattered;
+                scatter_pdf = None;
+            }
+            Some(ScatterRecord {
+                scatter: Scatter::Pdf(pdf),
+                ..
+            }) => {
+                if let Some(lights) = lights {
+                    let direct = sample_lights(&r, &rec, Some(pdf.as_ref()), camera, world, lights);
+                    color += throughput * direct;
+                }
+
+                let scattered = Ray::build_timed(rec.p, pdf.generate(), r.time());
+                let pdf_value = pdf.value(&scattered.direction());
+                if pdf_value <= 0.0 {
+                    break;
+                }
+                throughput = throughput * rec.mat.eval(&r, &rec, &scattered) / pdf_value;
+                r = scattered;
+                scatter_pdf = Some(pdf_value);
+            }
+        }
+
+        if depth + 1 >= camera.roulette_depth {
+            let survival = throughput.x().max(throughput.y()).max(throughput.z());
+            let survival = survival.min(0.95);
+            if random_f64() >= survival {
+                break;
+            }
+            throughput /= survival;
+        }
+    }
+
+    color
+}
+
+/// Accounts for the camera's fog between the origin of r and what it meets at t, or
+/// over its whole length if it meets nothing: the fog adds its own color to the
+/// path, and dims everything seen through it.
+fn apply_fog(camera: &Camera, r: &Ray, t: Option<f64>, color: &mut Color, throughput: &mut Color) {
+    if let Some(fog) = &camera.fog {
+        let transmittance = fog.transmittance(r, t.unwrap_or(f64::INFINITY));
+        *color += *throughput * fog.color * (1.0 - transmittance);
+        *throughput *= transmittance;
+    }
+}
+
+/// Returns the light scattered toward r_in from a shadow ray cast to a random point
+/// on the lights. When the material's own Pdf is given, the shadow ray is weighted
+/// against the chance of that Pdf having scattered the same way.
+fn sample_lights(
+    r_in: &Ray,
+    rec: &HitRecord,
+    scatter_pdf: Option<&dyn Pdf>,
+    camera: &Camera,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+) -> Color {
+    let light_pdf = HittablePdf::build(lights, rec.p);
+    let shadow_ray = Ray::build_timed(rec.p, light_pdf.generate(), r_in.time());
+    let light_pdf_value = light_pdf.value(&shadow_ray.direction());
+    if light_pdf_value <= 0.0 {
+        return Color::new();
+    }
+
+    let f = rec.mat.eval(r_in, rec, &shadow_ray);
+    if f.near_zero() {
+        return Color::new();
+    }
+
+    // Whatever surface the shadow ray hits first is what it sees; anything that
+    // doesn't emit leaves the point in shadow. Media along the way dim the light.
+    let mut light_rec = HitRecord::new();
+    if !world.shadow_hit(&shadow_ray, 0.001..=f64::INFINITY, &mut light_rec) {
+        return Color::new();
+    }
+    let mut emitted = light_rec.mat.emitted(&shadow_ray, &light_rec);
+    emitted *= world.transmittance(&shadow_ray, 0.001..=light_rec.t);
+    if let Some(fog) = &camera.fog {
+        emitted *= fog.transmittance(&shadow_ray, light_rec.t);
+    }
+
+    let weight = match scatter_pdf {
+        Some(pdf) => power_heuristic(light_pdf_value, pdf.value(&shadow_ray.direction())),
+        None => 1.0,
+    };
+    f * emitted * (weight / light_pdf_value)
+}
+
+/// Returns the multiple importance sampling weight, by the power heuristic, of a
+/// sample drawn with density pdf when the other strategy would have drawn it with
+/// density other_pdf.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    a / (a + b)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::camera::test::lit_floor;
+    use crate::hittable_list::HittableList;
+    use crate::material::Metal;
+    use crate::quad::Quad;
+    use crate::vec3::{Point3, Vec3};
+
+    fn mean(cam: &mut Camera, world: &HittableList, lights: &HittableList) -> f64 {
+        let image = cam.render_image(world, lights);
+        image.pixels.iter().map(|p| p.x()).sum::<f64>() / image.pixels.len() as f64
+    }
+
+    /// On a floor lit only from above, light bounces just once, so every integrator
+    /// but ambient occlusion sees the same image.
+    #[test]
+    fn test_integrators_agree() {
+        let (mut cam, world, lights) = lit_floor();
+        cam.samples_per_pixel = 1024;
+        let path = mean(&mut cam, &world, &lights);
+        for name in ["naive", "whitted"] {
+            cam.integrator = named(name).unwrap();
+            let other = mean(&mut cam, &world, &lights);
+            assert!((other / path - 1.0).abs() < 0.1, "{}", name);
+        }
+        assert!(named("radiosity").is_none());
+    }
+
+    #[test]
+    fn test_ambient_occlusion() {
+        let (mut cam, mut world, lights) = lit_floor();
+        cam.integrator = Arc::new(AmbientOcclusionIntegrator::build(1.0));
+        assert_eq!(mean(&mut cam, &world, &lights), 1.0);
+
+        // A mirror just above the floor hides it from the whole sky.
+        world.add(Quad::build(
+            Point3::build(-500.0, 0.5, -500.0),
+            Vec3::build(1000.0, 0.0, 0.0),
+            Vec3::build(0.0, 0.0, 1000.0),
+            Arc::new(Metal::build(1.0, 1.0, 1.0, 0.0)),
+        ));
+        cam.integrator = Arc::new(AmbientOcclusionIntegrator::build(100.0));
+        cam.look_from = Point3::build(0.0, 0.25, 0.0);
+        assert_eq!(mean(&mut cam, &world, &lights), 0.0);
+    }
+}
@@ -0,0 +1,313 @@
+//! A small JSON parser, enough to read scene description files.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// Describes a parsed JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+/// Describes why a JSON document could not be parsed, and where.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Value {
+    /// Returns the elements if the Value is an array.
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean if the Value is true or false.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if the Value is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Returns the members if the Value is an object.
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if the Value is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the member with the given key if the Value is an object containing it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object().and_then(|members| members.get(key))
+    }
+}
+
+/// Parses a complete JSON document.
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Recursive descent parser over the characters of a document.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Consumes the next character, failing unless it is the expected one.
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => {
+                self.pos = self.pos.saturating_sub(1);
+                Err(self.error(&format!("expected '{}'", expected)))
+            }
+        }
+    }
+
+    /// Builds a ParseError pointing at the current position.
+    fn error(&self, message: &str) -> ParseError {
+        let consumed = &self.chars[..self.pos.min(self.chars.len())];
+        let line = consumed.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = consumed.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+        ParseError {
+            message: message.to_string(),
+            line,
+            column,
+        }
+    }
+
+    /// Consumes and returns the next character.
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        self.pos += 1;
+        c
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(elements)),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected ',' or ']'"));
+                }
+            }
+        }
+    }
+
+    /// Consumes the four hex digits of a `\u` escape and returns their value.
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+        if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("invalid unicode escape"));
+        }
+        Ok(u32::from_str_radix(&hex, 16).unwrap())
+    }
+
+    /// Consumes a keyword such as `true`, returning the given Value.
+    fn parse_keyword(&mut self, keyword: &str, value: Value) -> Result<Value, ParseError> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(Value::Number).map_err(|_| {
+            self.pos = start;
+            self.error(&format!("invalid number '{}'", text))
+        })
+    }
+
+    fn parse_object(&mut self) -> Result<Value, ParseError> {
+        self.expect('{')?;
+        let mut members = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            members.insert(key, value);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Object(members)),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected ',' or '}'"));
+                }
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut code = self.parse_hex4()?;
+                        // Characters outside the Basic Multilingual Plane are escaped as
+                        // a UTF-16 surrogate pair.
+                        if (0xd800..0xdc00).contains(&code) {
+                            self.expect('\\')?;
+                            self.expect('u')?;
+                            let low = self.parse_hex4()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err(self.error("invalid unicode escape"));
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        let c = char::from_u32(code)
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        s.push(c);
+                    }
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => self.parse_string().map(Value::String),
+            Some('t') => self.parse_keyword("true", Value::Bool(true)),
+            Some('f') => self.parse_keyword("false", Value::Bool(false)),
+            Some('n') => self.parse_keyword("null", Value::Null),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    /// Returns the next character without consuming it.
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Skips whitespace, and `//` line comments so scene files can be annotated.
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('/') if self.chars.get(self.pos + 1) == Some(&'/') => {
+                    while !matches!(self.next(), Some('\n') | None) {}
+                }
+                _ => return,
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let value = parse(r#"{"a": [1, -2.5e1, true, null], "b": {"c": "d\n"}}"#).unwrap();
+        let a = value.get("a").unwrap().as_array().unwrap();
+        assert_eq!(a[1].as_f64(), Some(-25.0));
+        assert_eq!(a[2].as_bool(), Some(true));
+        assert_eq!(a[3], Value::Null);
+        assert_eq!(
+            value.get("b").unwrap().get("c").unwrap().as_str(),
+            Some("d\n")
+        );
+
+        let value = parse(r#""\u00e9\ud83d\ude00""#).unwrap();
+        assert_eq!(value.as_str(), Some("\u{e9}\u{1f600}"));
+        assert!(parse(r#""\ud83d""#).is_err());
+        assert!(parse(r#""\ud83d\u0041""#).is_err());
+
+        let err = parse("{\n  \"a\": [1 2]\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 11));
+    }
+}
@@ -1,24 +1,58 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
 
 // Modules
 
+pub mod aabb;
+pub mod aov;
+pub mod background;
+pub mod bvh;
 pub mod camera;
+pub mod constant_medium;
 pub mod hittable;
 pub mod hittable_list;
+pub mod image;
+pub mod image_io;
+pub mod integrator;
+pub mod json;
 pub mod material;
+pub mod noise;
+pub mod obj;
+pub mod onb;
+pub mod pdf;
+pub mod quad;
 pub mod ray;
+pub mod sampler;
+pub mod scene;
+pub mod scenes;
 pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
 pub mod vec3;
+pub mod volume;
 
 // Utility functions
 
+thread_local! {
+    // Each thread draws from its own generator, so that a seeded thread produces the
+    // same sequence no matter what other threads are doing.
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
 // Returns a random f64 in the range 0.0..=1.0
 pub fn random_f64() -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(0.0..=1.0)
+    RNG.with(|rng| rng.borrow_mut().gen_range(0.0..=1.0))
 }
 
 /// Returns a random f64 in the given range.
 pub fn random_f64_in(min: f64, max: f64) -> f64 {
     min + (max - min) * random_f64()
 }
+
+/// Reseeds the calling thread's random number generator, making every random value
+/// it draws afterwards reproducible.
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
@@ -1,122 +1,304 @@
-use raytracing_in_one_weekend::camera::Camera;
-use raytracing_in_one_weekend::hittable_list::HittableList;
-use raytracing_in_one_weekend::material::{Dielectric, Lambertian, Metal};
-use raytracing_in_one_weekend::sphere::Sphere;
-use raytracing_in_one_weekend::{random_f64, random_f64_in, vec3::*};
-use std::rc::Rc;
-
-fn main() {
-    // World
-
-    let mut world = HittableList::new();
-
-    // Image 18 - With Hollow Glass Sphere
-    /*
-    let material_ground = Rc::new(Lambertian::build(0.8, 0.8, 0.0));
-    let material_center = Rc::new(Lambertian::build(0.1, 0.2, 0.5));
-    let material_left = Rc::new(Dielectric::build(1.5));
-    let material_right = Rc::new(Metal::build(0.8, 0.6, 0.2, 0.0));
-
-    world.add(Sphere::build(
-        Point3::build(0.0, -100.5, -1.0),
-        100.0,
-        material_ground,
-    ));
-    world.add(Sphere::build(
-        Point3::build(0.0, 0.0, -1.0),
-        0.5,
-        material_center,
-    ));
-    world.add(Sphere::build(
-        Point3::build(-1.0, 0.0, -1.0),
-        0.5,
-        material_left.clone(),
-    ));
-    world.add(Sphere::build(
-        Point3::build(-1.0, 0.0, -1.0),
-        -0.4,
-        material_left,
-    ));
-    world.add(Sphere::build(
-        Point3::build(1.0, 0.0, -1.0),
-        0.5,
-        material_right,
-    ));
-    */
-
-    // Image 19 - Testing Camera FOV
-    /*
-    let R: f64 = (std::f64::consts::PI / 4.0).cos();
-
-    let material_left = Rc::new(Lambertian::build(0.0, 0.0, 1.0));
-    let material_right = Rc::new(Lambertian::build(1.0, 0.0, 0.0));
-
-    world.add(Sphere::build(Point3::build(-R, 0.0, -1.0), R, material_left));
-    world.add(Sphere::build(Point3::build(R, 0.0, -1.0), R, material_right));
-    */
-
-    // Book Cover Image
-    let ground_material = Rc::new(Lambertian::build(0.5, 0.5, 0.5));
-    world.add(Sphere::build(
-        Point3::build(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    ));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_f64();
-            let center = Point3::build(
-                a as f64 + 0.9 * random_f64(),
-                0.2,
-                b as f64 + 0.9 * random_f64(),
-            );
-
-            if (center - Point3::build(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo: Color = random_vec3() * random_vec3();
-                    let sphere_material = Rc::new(Lambertian::from(albedo));
-                    world.add(Sphere::build(center, 0.2, sphere_material));
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo: Color = random_vec3_in_range(0.5, 1.0);
-                    let fuzz = random_f64_in(0.0, 0.5);
-                    let sphere_material = Rc::new(Metal::from(albedo, fuzz));
-                    world.add(Sphere::build(center, 0.2, sphere_material));
-                } else {
-                    // glass
-                    let sphere_material = Rc::new(Dielectric::build(1.5));
-                    world.add(Sphere::build(center, 0.2, sphere_material));
+use raytracing_in_one_weekend::aov::Aov;
+use raytracing_in_one_weekend::bvh::{BvhNode, SplitHeuristic};
+use raytracing_in_one_weekend::image_io;
+use raytracing_in_one_weekend::integrator;
+use raytracing_in_one_weekend::sampler;
+use raytracing_in_one_weekend::scene::Scene;
+use raytracing_in_one_weekend::scenes;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Usage: raytracing-in-one-weekend [OPTIONS]
+
+Renders a scene, writing a PPM image to stdout unless an output file is given.
+
+Options:
+  -s, --scene <FILE>      Render the JSON scene file FILE
+  -b, --builtin <NAME>    Render a built-in scene: book-cover (default), hollow-glass,
+                          fov-test, cornell-box or cornell-smoke
+  -o, --output <FILE>     Write the image to FILE, as PNG, binary PPM or Radiance HDR
+                          depending on its extension (.png, .ppm, .hdr)
+  -w, --width <PIXELS>    Image width
+  -a, --aspect <RATIO>    Image aspect ratio, as a number or W:H (e.g. 16:9)
+      --spp <N>           Samples per pixel
+      --adaptive <ERROR>  Sample each pixel until its relative error falls below ERROR,
+                          spending the --spp budget where the image is noisiest
+      --min-spp <N>       Samples every pixel takes when sampling adaptively
+      --max-spp <N>       Most samples a pixel takes when sampling adaptively
+      --max-depth <N>     Maximum number of ray bounces
+      --rr-depth <N>      Bounces before Russian roulette may end a path
+  -i, --integrator <NAME> Light transport algorithm: path (default), naive, ao
+                          (ambient occlusion) or whitted
+      --aov <LIST>        Also render the comma-separated AOVs in LIST, each written
+                          beside the output file as FILE.<aov>.<ext>: normal, depth,
+                          albedo, uv, front_face or object_id. Use .hdr to keep
+                          their values exact
+      --sampler <NAME>    Source of sample positions: random (default), stratified,
+                          halton, sobol or blue_noise
+  -j, --threads <N>       Worker thread count (0 uses all available cores)
+      --seed <N>          Seed for random sampling and the book-cover scene layout
+  -h, --help              Print this help";
+
+/// Settings given on the command line. Unset options keep the scene's values.
+#[derive(Default)]
+struct Options {
+    scene: Option<PathBuf>,
+    builtin: Option<String>,
+    output: Option<PathBuf>,
+    width: Option<i32>,
+    aspect_ratio: Option<f64>,
+    samples_per_pixel: Option<i32>,
+    adaptive_threshold: Option<f64>,
+    min_samples_per_pixel: Option<i32>,
+    max_samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    roulette_depth: Option<i32>,
+    integrator: Option<String>,
+    aovs: Vec<Aov>,
+    sampler: Option<String>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+    help: bool,
+}
+
+impl Options {
+    /// Parses the options from the program arguments, excluding the program name.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for '{}'", arg))
+            };
+            match arg.as_str() {
+                "-s" | "--scene" => options.scene = Some(PathBuf::from(value()?)),
+                "-b" | "--builtin" => options.builtin = Some(value()?),
+                "-o" | "--output" => options.output = Some(PathBuf::from(value()?)),
+                "-w" | "--width" => options.width = Some(parse_integer(&arg, &value()?, 1)?),
+                "-a" | "--aspect" => options.aspect_ratio = Some(parse_aspect(&value()?)?),
+                "--spp" => options.samples_per_pixel = Some(parse_integer(&arg, &value()?, 1)?),
+                "--adaptive" => options.adaptive_threshold = Some(parse_number(&arg, &value()?)?),
+                "--min-spp" => {
+                    options.min_samples_per_pixel = Some(parse_integer(&arg, &value()?, 1)?)
+                }
+                "--max-spp" => {
+                    options.max_samples_per_pixel = Some(parse_integer(&arg, &value()?, 1)?)
                 }
+                "--max-depth" => options.max_depth = Some(parse_integer(&arg, &value()?, 1)?),
+                "--rr-depth" => options.roulette_depth = Some(parse_integer(&arg, &value()?, 0)?),
+                "-i" | "--integrator" => options.integrator = Some(value()?),
+                "--aov" => options.aovs.extend(parse_aovs(&value()?)?),
+                "--sampler" => options.sampler = Some(value()?),
+                "-j" | "--threads" => options.threads = Some(parse_number(&arg, &value()?)?),
+                "--seed" => options.seed = Some(parse_number(&arg, &value()?)?),
+                "-h" | "--help" => options.help = true,
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+        }
+
+        if let Some(output) = &options.output {
+            if !image_io::is_supported(output) {
+                return Err(format!(
+                    "unsupported output format '{}' (expected .{})",
+                    output.display(),
+                    image_io::EXTENSIONS.join(", .")
+                ));
+            }
+        }
+        if !options.aovs.is_empty() && options.output.is_none() {
+            return Err("'--aov' needs an output file".to_string());
+        }
+        if options.scene.is_some() && options.builtin.is_some() {
+            return Err("'--scene' and '--builtin' cannot be used together".to_string());
+        }
+        Ok(options)
+    }
+
+    /// Loads the scene selected by the options and applies the camera overrides.
+    fn load_scene(&self) -> Result<Scene, String> {
+        let mut scene = match (&self.scene, &self.builtin) {
+            (Some(path), _) => {
+                Scene::load(path).map_err(|err| format!("{}: {}", path.display(), err))?
+            }
+            (None, name) => {
+                let name = name.as_deref().unwrap_or("book-cover");
+                scenes::builtin(name, self.seed).ok_or_else(|| {
+                    format!(
+                        "unknown built-in scene '{}' (expected one of: {})",
+                        name,
+                        scenes::BUILTIN_NAMES.join(", ")
+                    )
+                })?
             }
+        };
+
+        let cam = &mut scene.camera;
+        if let Some(width) = self.width {
+            cam.image_width = width;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            cam.aspect_ratio = aspect_ratio;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            cam.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(adaptive_threshold) = self.adaptive_threshold {
+            cam.adaptive_threshold = adaptive_threshold;
+        }
+        if let Some(min_samples_per_pixel) = self.min_samples_per_pixel {
+            cam.min_samples_per_pixel = min_samples_per_pixel;
+        }
+        if let Some(max_samples_per_pixel) = self.max_samples_per_pixel {
+            cam.max_samples_per_pixel = max_samples_per_pixel;
         }
+        if let Some(max_depth) = self.max_depth {
+            cam.max_depth = max_depth;
+        }
+        if let Some(roulette_depth) = self.roulette_depth {
+            cam.roulette_depth = roulette_depth;
+        }
+        if let Some(name) = &self.integrator {
+            cam.integrator = integrator::named(name).ok_or_else(|| {
+                format!(
+                    "unknown integrator '{}' (expected one of: {})",
+                    name,
+                    integrator::INTEGRATOR_NAMES.join(", ")
+                )
+            })?;
+        }
+        if let Some(name) = &self.sampler {
+            cam.sampler = sampler::named(name).ok_or_else(|| {
+                format!(
+                    "unknown sampler '{}' (expected one of: {})",
+                    name,
+                    sampler::SAMPLER_NAMES.join(", ")
+                )
+            })?;
+        }
+        if let Some(threads) = self.threads {
+            cam.threads = threads;
+        }
+        if let Some(seed) = self.seed {
+            cam.seed = seed;
+        }
+        Ok(scene)
     }
+}
+
+/// Parses a comma-separated list of AOV names.
+fn parse_aovs(value: &str) -> Result<Vec<Aov>, String> {
+    value
+        .split(',')
+        .map(|name| {
+            Aov::from_name(name.trim()).ok_or_else(|| {
+                let names: Vec<&str> = Aov::ALL.iter().map(Aov::name).collect();
+                format!(
+                    "unknown AOV '{}' (expected one of: {})",
+                    name,
+                    names.join(", ")
+                )
+            })
+        })
+        .collect()
+}
 
-    let material1 = Rc::new(Dielectric::build(1.5));
-    world.add(Sphere::build(Point3::build(0.0, 1.0, 0.0), 1.0, material1));
+/// Returns the path an AOV is written to beside the output file: FILE.<aov>.<ext>.
+fn aov_path(output: &Path, aov: Aov) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, aov, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, aov),
+    };
+    output.with_file_name(name)
+}
+
+/// Parses a numeric option value.
+fn parse_number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for '{}'", value, option))
+}
 
-    let material2 = Rc::new(Lambertian::build(0.4, 0.2, 0.1));
-    world.add(Sphere::build(Point3::build(-4.0, 1.0, 0.0), 1.0, material2));
+/// Parses an integer option value, which must be at least min.
+fn parse_integer(option: &str, value: &str, min: i32) -> Result<i32, String> {
+    match value.parse() {
+        Ok(n) if n >= min => Ok(n),
+        _ => Err(format!(
+            "invalid value '{}' for '{}' (expected an integer of at least {})",
+            value, option, min
+        )),
+    }
+}
 
-    let material3 = Rc::new(Metal::build(0.7, 0.6, 0.5, 0.0));
-    world.add(Sphere::build(Point3::build(4.0, 1.0, 0.0), 1.0, material3));
+/// Parses an aspect ratio given either as a number or as W:H.
+fn parse_aspect(value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = parse_number("--aspect", w)?;
+            let h: f64 = parse_number("--aspect", h)?;
+            w / h
+        }
+        None => parse_number("--aspect", value)?,
+    };
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("invalid value '{}' for '--aspect'", value))
+    }
+}
 
-    // Camera
-    let mut cam = Camera::new();
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
 
-    cam.aspect_ratio = 16.0 / 9.0;
-    cam.image_width = 1200;
-    cam.samples_per_pixel = 100;
-    cam.max_depth = 50;
+    let Scene {
+        mut camera,
+        world,
+        lights,
+    } = match options.load_scene() {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let world = BvhNode::build(world, SplitHeuristic::Sah);
 
-    cam.vfov = 20.0;
-    cam.look_from = Point3::build(13.0, 2.0, 3.0);
-    cam.look_at = Point3::build(0.0, 0.0, 0.0);
-    cam.vup = Vec3::build(0.0, 1.0, 0.0);
+    let image = camera.render_image(&world, &lights);
+    let written = match &options.output {
+        Some(path) => image_io::write_image(&image, path)
+            .map_err(|err| format!("{}: {}", path.display(), err)),
+        None => image_io::write_p3(&image, &mut io::stdout().lock()).map_err(|err| err.to_string()),
+    };
+    if let Err(err) = written {
+        eprintln!("error: {}", err);
+        return ExitCode::FAILURE;
+    }
 
-    cam.defocus_angle = 0.6;
-    cam.focus_dist = 10.0;
+    if let Some(output) = &options.output {
+        for &aov in &options.aovs {
+            let path = aov_path(output, aov);
+            let image = camera.render_aov(&world, aov);
+            if let Err(err) = image_io::write_image(&image, &path) {
+                eprintln!("error: {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    eprintln!("\rDone.\n");
 
-    cam.render(&world);
+    ExitCode::SUCCESS
 }
@@ -1,45 +1,90 @@
 use crate::hittable::HitRecord;
+use crate::pdf::{CosinePdf, Pdf, UniformSpherePdf};
 use crate::random_f64;
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::{dot, random_unit_vector, reflect, refract, unit_vector, Color};
+use std::f64::consts::PI;
 use std::fmt::Display;
+use std::sync::Arc;
 
 /// Describes a material with Dielectric properties such as glass.
 pub struct Dielectric {
     ir: f64, // Index of Refraction
 }
 
+/// Describes a material that emits light and scatters none.
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+/// Describes the phase function of a participating medium, which scatters light
+/// equally in every direction.
+pub struct Isotropic {
+    tex: Arc<dyn Texture>,
+}
+
 /// Describes a material with Lambertian reflectance.
 pub struct Lambertian {
-    pub albedo: Color,
+    tex: Arc<dyn Texture>,
 }
 
 /// Describes a material with mirror-like reflectance.
 pub struct Metal {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
     fuzz: f64,
 }
 
-// Required Display for debugging purposes.
-pub trait Material: std::fmt::Display {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool;
+/// Describes how a material scatters a Ray that hits it.
+pub struct ScatterRecord {
+    pub attenuation: Color, // Fraction of the light scattered, for each channel
+    pub scatter: Scatter,
+}
+
+/// Describes where the light scattered by a material goes.
+pub enum Scatter {
+    /// Over a spread of directions distributed by the Pdf. The light carried by a
+    /// direction d is weighted by the material's `eval` for d, divided by the Pdf's
+    /// value at d, so directions can also be drawn from other distributions, such as
+    /// one toward the lights.
+    Pdf(Box<dyn Pdf>),
+    /// Along the single Ray, as by a mirror or glass, weighted by the attenuation.
+    /// No other direction can be sampled.
+    Specular(Ray),
+}
+
+// Required Display for debugging purposes, and Send + Sync for sharing between
+// render threads.
+pub trait Material: std::fmt::Display + Send + Sync {
+    /// Returns how a Ray hitting the material is scattered, or None if it is absorbed.
+    /// Most materials scatter nothing.
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    /// Returns the fraction of the light arriving along scattered that leaves along
+    /// r_in, per unit solid angle: the BSDF times the cosine of the angle scattered
+    /// makes with the normal. Specular materials, which scatter into no spread of
+    /// directions, return black.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Color {
+        Color::new()
+    }
+
+    /// Returns the light emitted from the surface at the hit point. Most materials
+    /// emit nothing.
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new()
+    }
+
+    /// Returns the fraction of light the material scatters at the hit point, whatever
+    /// the direction. Materials that scatter nothing return black.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new()
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        *attenuation = Color::build(1.0, 1.0, 1.0);
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
         } else {
@@ -59,44 +104,76 @@ impl Material for Dielectric {
                 refract(&unit_direction, &rec.normal, refraction_ratio)
             };
 
-        *scattered = Ray::build(rec.p, direction);
-        true
+        Some(ScatterRecord {
+            attenuation: Color::build(1.0, 1.0, 1.0),
+            scatter: Scatter::Specular(Ray::build_timed(rec.p, direction, r_in.time())),
+        })
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::build(1.0, 1.0, 1.0)
+    }
+}
+
+impl Material for DiffuseLight {
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, &rec.p),
+            scatter: Scatter::Pdf(Box::new(UniformSpherePdf)),
+        })
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, _scattered: &Ray) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p) / (4.0 * PI)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        _r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + random_unit_vector();
-
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, &rec.p),
+            scatter: Scatter::Pdf(Box::new(CosinePdf::build(&rec.normal))),
+        })
+    }
+
+    /// The Lambertian BSDF is albedo / pi in every direction above the surface.
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let cos_theta = dot(&rec.normal, &unit_vector(&scattered.direction()));
+        self.tex.value(rec.u, rec.v, &rec.p) * (cos_theta.max(0.0) / PI)
+    }
 
-        *scattered = Ray::build(rec.p, scatter_direction);
-        *attenuation = self.albedo;
-        true
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
     }
 }
 
 impl Material for Metal {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let reflected = reflect(&unit_vector(&r_in.direction()), &rec.normal);
-        *scattered = Ray::build(rec.p, reflected + self.fuzz * random_unit_vector());
-        *attenuation = self.albedo;
-        dot(&scattered.direction(), &rec.normal) > 0.
+        let direction = reflected + self.fuzz * random_unit_vector();
+
+        // Fuzzed reflections that would leave below the surface are absorbed.
+        if dot(&direction, &rec.normal) <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord {
+            attenuation: self.tex.value(rec.u, rec.v, &rec.p),
+            scatter: Scatter::Specular(Ray::build_timed(rec.p, direction, r_in.time())),
+        })
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
     }
 }
 
@@ -106,23 +183,27 @@ impl Display for Dielectric {
     }
 }
 
+impl Display for DiffuseLight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tex)
+    }
+}
+
+impl Display for Isotropic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tex)
+    }
+}
+
 impl Display for Lambertian {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} {} {}",
-            self.albedo.e[0], self.albedo.e[1], self.albedo.e[2]
-        )
+        write!(f, "{}", self.tex)
     }
 }
 
 impl Display for Metal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} {} {}",
-            self.albedo.e[0], self.albedo.e[1], self.albedo.e[2]
-        )
+        write!(f, "{}", self.tex)
     }
 }
 
@@ -142,43 +223,84 @@ impl Dielectric {
     }
 }
 
+impl DiffuseLight {
+    /// Builds a new DiffuseLight from RGB values. Values above 1.0 make brighter lights.
+    pub fn build(r: f64, g: f64, b: f64) -> Self {
+        Self::from(Color::build(r, g, b))
+    }
+
+    /// Constructs a new DiffuseLight from an existing Color.
+    pub fn from(emit: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::from(emit)))
+    }
+
+    /// Constructs a new DiffuseLight emitting the colors of a Texture.
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Isotropic {
+    /// Builds a new Isotropic from RGB values.
+    pub fn build(r: f64, g: f64, b: f64) -> Self {
+        Self::from(Color::build(r, g, b))
+    }
+
+    /// Constructs a new Isotropic from an existing Color.
+    pub fn from(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::from(albedo)))
+    }
+
+    /// Constructs a new Isotropic whose albedo varies with a Texture.
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
 impl Lambertian {
     /// Builds a new Lambertian from RGB values.
     pub fn build(r: f64, g: f64, b: f64) -> Self {
-        Self {
-            albedo: Color::build(r, g, b),
-        }
+        Self::from(Color::build(r, g, b))
     }
 
-    /// Returns the albedo of the Lambertian.
-    pub fn color(&self) -> Color {
-        self.albedo
+    /// Returns the albedo of the Lambertian, or None if it varies with a texture. The
+    /// albedo at a hit point is given by `Material::albedo`.
+    pub fn color(&self) -> Option<Color> {
+        self.tex.solid_color()
     }
 
     /// Constructs a new Lambertian fron an existing Color.
     pub fn from(albedo: Color) -> Self {
-        Self { albedo }
+        Self::from_texture(Arc::new(SolidColor::from(albedo)))
+    }
+
+    /// Constructs a new Lambertian whose albedo varies with a Texture.
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
     }
 }
 
 impl Metal {
     /// Builds a new Metal from RGB and fuzz values.
     pub fn build(r: f64, g: f64, b: f64, fuzz: f64) -> Self {
-        Self {
-            albedo: Color::build(r, g, b),
-            fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
-        }
+        Self::from(Color::build(r, g, b), fuzz)
     }
 
-    /// Returns the albedo of the Metal.
-    pub fn color(&self) -> Color {
-        self.albedo
+    /// Returns the albedo of the Metal, or None if it varies with a texture. The
+    /// albedo at a hit point is given by `Material::albedo`.
+    pub fn color(&self) -> Option<Color> {
+        self.tex.solid_color()
     }
 
     /// Constructs a new Metal from an existing Color and a fuzz value.
     pub fn from(albedo: Color, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::from(albedo)), fuzz)
+    }
+
+    /// Constructs a new Metal whose albedo varies with a Texture, and a fuzz value.
+    pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self {
-            albedo,
+            tex,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         }
     }
@@ -0,0 +1,217 @@
+//! Procedural noise functions, used by the solid textures to add surface detail
+//! without image assets.
+
+use crate::vec3::{dot, unit_vector, Point3, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const POINT_COUNT: usize = 256;
+
+/// Describes a Perlin gradient noise generator. Its random tables come from its own
+/// seed, so a texture looks the same on every run and on every render thread.
+pub struct Perlin {
+    ranvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+/// Describes a Worley (cellular) noise generator, which scatters one feature point in
+/// every unit cell of space and measures the distance to the nearest one.
+pub struct Worley {
+    seed: u64,
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::build(0)
+    }
+}
+
+impl Default for Worley {
+    fn default() -> Self {
+        Self::build(0)
+    }
+}
+
+impl Perlin {
+    /// Builds a new Perlin generator whose tables are drawn from the given seed.
+    pub fn build(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ranvec = (0..POINT_COUNT)
+            .map(|_| {
+                unit_vector(&Vec3::build(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                ))
+            })
+            .collect();
+
+        Self {
+            ranvec,
+            perm_x: Self::generate_perm(&mut rng),
+            perm_y: Self::generate_perm(&mut rng),
+            perm_z: Self::generate_perm(&mut rng),
+        }
+    }
+
+    /// Returns a random permutation of 0..POINT_COUNT.
+    fn generate_perm(rng: &mut StdRng) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        for i in (1..POINT_COUNT).rev() {
+            let target = rng.gen_range(0..=i);
+            p.swap(i, target);
+        }
+        p
+    }
+
+    /// Constructs a new Perlin generator from the default seed.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns smoothly varying noise at p, in the range -1.0..=1.0.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let u = p.x() - p.x().floor();
+        let v = p.y() - p.y().floor();
+        let w = p.z() - p.z().floor();
+
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        let mut c = [[[Vec3::new(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let x = self.perm_x[((i + di as i64) & 255) as usize];
+                    let y = self.perm_y[((j + dj as i64) & 255) as usize];
+                    let z = self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.ranvec[x ^ y ^ z];
+                }
+            }
+        }
+
+        Self::perlin_interp(&c, u, v, w)
+    }
+
+    /// Trilinearly interpolates the corner gradients, with Hermite smoothing to hide
+    /// the grid.
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accum = 0.0;
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::build(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * dot(corner, &weight_v);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Returns turbulence at p: the absolute sum of depth octaves of noise, each at
+    /// twice the frequency and half the weight of the last.
+    pub fn turb(&self, p: &Point3, depth: i32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+        accum.abs()
+    }
+}
+
+impl Worley {
+    /// Builds a new Worley generator whose feature points are placed by the given seed.
+    pub fn build(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Returns the position of the feature point in the cell with the given corner.
+    fn feature_point(&self, i: i64, j: i64, k: i64) -> Point3 {
+        let mut h = self.seed;
+        for n in [i, j, k] {
+            h = splitmix64(h ^ n as u64);
+        }
+        let unit = |bits: u64| (bits >> 11) as f64 / (1u64 << 53) as f64;
+        Point3::build(
+            i as f64 + unit(h),
+            j as f64 + unit(splitmix64(h)),
+            k as f64 + unit(splitmix64(h ^ 1)),
+        )
+    }
+
+    /// Constructs a new Worley generator from the default seed.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the distance from p to the nearest feature point, clamped to 0.0..=1.0.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let i = p.x().floor() as i64;
+        let j = p.y().floor() as i64;
+        let k = p.z().floor() as i64;
+
+        // The nearest point is always in this cell or one of its 26 neighbors.
+        let mut nearest = f64::INFINITY;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let feature = self.feature_point(i + di, j + dj, k + dk);
+                    nearest = nearest.min((feature - *p).length_squared());
+                }
+            }
+        }
+        nearest.sqrt().min(1.0)
+    }
+}
+
+/// Mixes the bits of x, so that nearby inputs give unrelated outputs.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_noise() {
+        let perlin = Perlin::build(7);
+        let worley = Worley::build(7);
+        let p = Point3::build(1.3, -2.7, 0.4);
+
+        // Same seed, same noise.
+        assert_eq!(perlin.noise(&p), Perlin::build(7).noise(&p));
+        assert_eq!(worley.noise(&p), Worley::build(7).noise(&p));
+
+        // Gradient noise vanishes on the lattice.
+        assert_eq!(perlin.noise(&Point3::build(3.0, -1.0, 2.0)), 0.0);
+
+        for n in 0..100 {
+            let q = Point3::build(n as f64 * 0.37, n as f64 * -0.21, n as f64 * 0.13);
+            assert!((-1.0..=1.0).contains(&perlin.noise(&q)));
+            assert!(perlin.turb(&q, 7) >= 0.0);
+            assert!((0.0..=1.0).contains(&worley.noise(&q)));
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Loads triangle meshes from Wavefront OBJ files and their MTL material libraries.
+//!
+//! The loader reads vertex positions (`v`), texture coordinates (`vt`), normals (`vn`)
+//! and polygonal faces (`f`), splitting polygons into triangle fans. Faces are grouped
+//! into one TriangleMesh per material selected with `usemtl`. MTL materials map to
+//! the closest material of this crate:
+//!
+//! - an emission color (`Ke`) makes a DiffuseLight,
+//! - transparency (`d` < 1, `Tr` > 0, or `illum` 4, 6, 7 or 9) makes a Dielectric with
+//!   index of refraction `Ni`,
+//! - a specular color (`Ks`) brighter than the diffuse one, or `illum` 3 or 5, makes a
+//!   Metal whose fuzz falls as the shininess `Ns` rises,
+//! - anything else is Lambertian, textured by `map_Kd` when it names an image.
+
+use crate::hittable_list::HittableList;
+use crate::image_io;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::texture::ImageTexture;
+use crate::triangle::{MeshFace, TriangleMesh};
+use crate::vec3::{Color, Point3, Vec3};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Loads the OBJ file at path as a HittableList of mesh triangles. Material libraries
+/// and texture images are found relative to the directory holding the OBJ file. Faces
+/// with no material, or a material missing from the libraries, use default_material.
+pub fn load_obj(path: &Path, default_material: Arc<dyn Material>) -> io::Result<HittableList> {
+    let text = std::fs::read_to_string(path)?;
+    parse_obj(
+        &text,
+        path.parent().unwrap_or(Path::new("")),
+        default_material,
+    )
+}
+
+/// Builds a HittableList of mesh triangles from the text of an OBJ file, finding the
+/// files it refers to relative to base_dir.
+pub fn parse_obj(
+    text: &str,
+    base_dir: &Path,
+    default_material: Arc<dyn Material>,
+) -> io::Result<HittableList> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+
+    let mut materials = HashMap::new();
+    let mut groups: Vec<(Option<String>, Vec<MeshFace>)> = vec![(None, Vec::new())];
+
+    for (n, line) in text.lines().enumerate() {
+        let error = |message: &str| invalid_line(n + 1, message);
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&mut tokens).ok_or_else(|| error("bad vertex"))?),
+            "vn" => normals.push(parse_vec3(&mut tokens).ok_or_else(|| error("bad normal"))?),
+            "vt" => {
+                let u = tokens.next().and_then(|t| t.parse().ok());
+                let v = tokens.next().map_or(Some(0.0), |t| t.parse().ok());
+                match (u, v) {
+                    (Some(u), Some(v)) => uvs.push((u, v)),
+                    _ => return Err(error("bad texture coordinate")),
+                }
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|t| parse_corner(t, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|corners| corners.len() >= 3)
+                    .ok_or_else(|| error("bad face"))?;
+
+                // Split the polygon into a fan of triangles around its first corner.
+                let faces = &mut groups.last_mut().unwrap().1;
+                for k in 1..corners.len() - 1 {
+                    let tri = [corners[0], corners[k], corners[k + 1]];
+                    let all = |index: fn(&Corner) -> Option<usize>| {
+                        tri.iter()
+                            .map(index)
+                            .collect::<Option<Vec<_>>>()
+                            .map(|i| [i[0], i[1], i[2]])
+                    };
+                    faces.push(MeshFace {
+                        positions: tri.map(|c| c.position),
+                        normals: all(|c| c.normal),
+                        uvs: all(|c| c.uv),
+                    });
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                groups.push((Some(name), Vec::new()));
+            }
+            "mtllib" => {
+                for file in tokens {
+                    let path = base_dir.join(file);
+                    let text = std::fs::read_to_string(&path).map_err(|err| {
+                        io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+                    })?;
+                    materials.extend(parse_mtl(&text, base_dir).map_err(|err| {
+                        io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+                    })?);
+                }
+            }
+            _ => {} // Groups, objects and smoothing groups don't affect the render.
+        }
+    }
+
+    let mut list = HittableList::new();
+    for (name, faces) in groups.into_iter().filter(|(_, faces)| !faces.is_empty()) {
+        let mat = name
+            .and_then(|name| materials.get(&name).cloned())
+            .unwrap_or_else(|| Arc::clone(&default_material));
+        let mesh = compact_mesh(&positions, &normals, &uvs, faces, mat);
+        list.objects.extend(mesh.triangles().objects);
+    }
+    Ok(list)
+}
+
+/// Returns the materials defined by the text of an MTL file, by name. Texture images
+/// are found relative to base_dir.
+pub fn parse_mtl(text: &str, base_dir: &Path) -> io::Result<HashMap<String, Arc<dyn Material>>> {
+    let mut descriptions: Vec<(String, MtlDescription)> = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let error = |message: &str| invalid_line(n + 1, message);
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            descriptions.push((name, MtlDescription::default()));
+            continue;
+        }
+        let Some((_, mtl)) = descriptions.last_mut() else {
+            continue;
+        };
+
+        let number = |mut tokens: std::str::SplitWhitespace| {
+            tokens
+                .next()
+                .and_then(|t| t.parse::<f64>().ok())
+                .ok_or_else(|| error(&format!("bad '{}'", keyword)))
+        };
+        match keyword {
+            "Kd" => mtl.kd = parse_vec3(&mut tokens).ok_or_else(|| error("bad 'Kd'"))?,
+            "Ks" => mtl.ks = parse_vec3(&mut tokens).ok_or_else(|| error("bad 'Ks'"))?,
+            "Ke" => mtl.ke = parse_vec3(&mut tokens).ok_or_else(|| error("bad 'Ke'"))?,
+            "Ns" => mtl.ns = number(tokens)?,
+            "Ni" => mtl.ni = number(tokens)?,
+            "d" => mtl.d = number(tokens)?,
+            "Tr" => mtl.d = 1.0 - number(tokens)?,
+            "illum" => mtl.illum = number(tokens)? as i32,
+            // Options such as "-bm 1" may come before the file name, which is last.
+            "map_Kd" => mtl.map_kd = tokens.last().map(str::to_string),
+            _ => {}
+        }
+    }
+
+    descriptions
+        .into_iter()
+        .map(|(name, mtl)| Ok((name, mtl.material(base_dir)?)))
+        .collect()
+}
+
+/// Describes one corner of an OBJ face by zero-based indices into the vertex buffers.
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Describes the MTL statements of a material that this crate can make use of.
+struct MtlDescription {
+    kd: Color,
+    ks: Color,
+    ke: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: i32,
+    map_kd: Option<String>,
+}
+
+impl Default for MtlDescription {
+    fn default() -> Self {
+        Self {
+            kd: Color::build(0.8, 0.8, 0.8),
+            ks: Color::new(),
+            ke: Color::new(),
+            ns: 0.0,
+            ni: 1.5,
+            d: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+impl MtlDescription {
+    /// Returns the material of this crate closest to the MTL description.
+    fn material(&self, base_dir: &Path) -> io::Result<Arc<dyn Material>> {
+        let max = |c: &Color| c.x().max(c.y()).max(c.z());
+
+        if max(&self.ke) > 0.0 {
+            return Ok(Arc::new(DiffuseLight::from(self.ke)));
+        }
+        if self.d < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            return Ok(Arc::new(Dielectric::build(self.ni)));
+        }
+        if matches!(self.illum, 3 | 5) || max(&self.ks) > max(&self.kd) {
+            // Map the Phong exponent to fuzz the way roughness maps to it: shinier
+            // surfaces blur their reflections less.
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            return Ok(Arc::new(Metal::from(self.ks, fuzz)));
+        }
+        match &self.map_kd {
+            Some(file) => {
+                let path = base_dir.join(file);
+                let image = image_io::read_image(&path).map_err(|err| {
+                    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+                })?;
+                Ok(Arc::new(Lambertian::from_texture(Arc::new(
+                    ImageTexture::build(image),
+                ))))
+            }
+            None => Ok(Arc::new(Lambertian::from(self.kd))),
+        }
+    }
+}
+
+/// Builds a TriangleMesh holding only the vertices its faces use, renumbering the
+/// faces to match.
+fn compact_mesh(
+    positions: &[Point3],
+    normals: &[Vec3],
+    uvs: &[(f64, f64)],
+    mut faces: Vec<MeshFace>,
+    material: Arc<dyn Material>,
+) -> TriangleMesh {
+    fn remap<T: Copy>(
+        index: &mut usize,
+        source: &[T],
+        used: &mut HashMap<usize, usize>,
+        out: &mut Vec<T>,
+    ) {
+        *index = *used.entry(*index).or_insert_with(|| {
+            out.push(source[*index]);
+            out.len() - 1
+        });
+    }
+
+    let mut mesh = TriangleMesh::build(Vec::new(), Vec::new(), material);
+    let (mut used_p, mut used_n, mut used_uv) = (HashMap::new(), HashMap::new(), HashMap::new());
+    for face in &mut faces {
+        for i in &mut face.positions {
+            remap(i, positions, &mut used_p, &mut mesh.positions);
+        }
+        for i in face.normals.iter_mut().flatten() {
+            remap(i, normals, &mut used_n, &mut mesh.normals);
+        }
+        for i in face.uvs.iter_mut().flatten() {
+            remap(i, uvs, &mut used_uv, &mut mesh.uvs);
+        }
+    }
+    mesh.faces = faces;
+    mesh
+}
+
+/// Parses a face corner, `v`, `v/vt`, `v//vn` or `v/vt/vn`, given the number of each
+/// kind of vertex read so far. OBJ indices count from 1, or back from the end when
+/// negative.
+fn parse_corner(token: &str, positions: usize, uvs: usize, normals: usize) -> Option<Corner> {
+    let resolve = |field: &str, count: usize| -> Option<usize> {
+        let index: i64 = field.parse().ok()?;
+        let index = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+        (0..count as i64).contains(&index).then_some(index as usize)
+    };
+    let optional = |field: Option<&str>, count| match field {
+        None | Some("") => Some(None),
+        Some(field) => resolve(field, count).map(Some),
+    };
+
+    let mut fields = token.split('/');
+    Some(Corner {
+        position: resolve(fields.next()?, positions)?,
+        uv: optional(fields.next(), uvs)?,
+        normal: optional(fields.next(), normals)?,
+    })
+}
+
+/// Parses three numbers from the tokens.
+fn parse_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<Vec3> {
+    let mut next = || tokens.next()?.parse::<f64>().ok();
+    Some(Vec3::build(next()?, next()?, next()?))
+}
+
+fn invalid_line(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::ray::Ray;
+
+    #[test]
+    fn test_parse_obj() {
+        // A unit square in z = 0 as one quad, with normals tilted toward +x, and a
+        // triangle behind it given with negative indices.
+        let text = "
+            # square
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0.6 0 0.8
+            f 1/1/1 2/2/1 3/3/1 4/4/1
+            v 0 0 -1
+            v 1 0 -1
+            v 0 1 -1
+            f -3 -2 -1
+        ";
+        let mat: Arc<dyn Material> = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        let list = parse_obj(text, Path::new(""), mat.clone()).unwrap();
+        assert_eq!(list.objects.len(), 3);
+
+        let mut rec = HitRecord::new();
+        let r = Ray::build(Point3::build(0.25, 0.75, 1.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(list.hit(&r, 0.0..=f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.normal, Vec3::build(0.6, 0.0, 0.8));
+        assert_eq!((rec.u, rec.v), (0.25, 0.75));
+
+        let err = parse_obj("v 0 0 0\nf 1 2 3\n", Path::new(""), mat)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "line 2: bad face");
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let text = "
+            newmtl matte
+            Kd 0.2 0.4 0.6
+            newmtl chrome
+            Kd 0 0 0
+            Ks 0.9 0.9 0.9
+            Ns 200
+            newmtl glass
+            Ni 1.33
+            d 0.1
+            newmtl lamp
+            Ke 4 4 4
+        ";
+        let materials = parse_mtl(text, Path::new("")).unwrap();
+        assert_eq!(materials.len(), 4);
+        assert_eq!(materials["matte"].to_string(), "0.2 0.4 0.6");
+        assert_eq!(materials["glass"].to_string(), "1.33");
+        assert_eq!(materials["lamp"].to_string(), "4 4 4");
+        assert_eq!(materials["chrome"].to_string(), "0.9 0.9 0.9");
+    }
+}
@@ -0,0 +1,58 @@
+use crate::vec3::{cross, unit_vector, Vec3};
+
+/// Describes an orthonormal basis: three perpendicular unit vectors u, v and w, built
+/// around a given w. Used to turn directions sampled around the Z axis into directions
+/// around any other axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Builds a new Onb whose w axis points along n.
+    pub fn build(n: &Vec3) -> Self {
+        let w = unit_vector(n);
+
+        // Any vector not parallel to w gives the other two axes.
+        let a = if w.x().abs() > 0.9 {
+            Vec3::build(0.0, 1.0, 0.0)
+        } else {
+            Vec3::build(1.0, 0.0, 0.0)
+        };
+        let v = unit_vector(&cross(&w, &a));
+        let u = cross(&w, &v);
+
+        Self { u, v, w }
+    }
+
+    /// Returns the vector whose coordinates in this basis are those of a.
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vec3::dot;
+
+    #[test]
+    fn test_onb() {
+        for n in [
+            Vec3::build(0.0, 0.0, 2.0),
+            Vec3::build(-1.0, 0.0, 0.0),
+            Vec3::build(1.0, 2.0, 3.0),
+        ] {
+            let onb = Onb::build(&n);
+            assert!((onb.w - unit_vector(&n)).near_zero());
+            assert!(dot(&onb.u, &onb.v).abs() < 1e-12);
+            assert!(dot(&onb.u, &onb.w).abs() < 1e-12);
+            assert!((onb.u.length() - 1.0).abs() < 1e-12);
+            assert!((onb.local(&Vec3::build(0.0, 0.0, 1.0)) - onb.w).near_zero());
+        }
+    }
+}
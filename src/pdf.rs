@@ -0,0 +1,146 @@
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::random_f64;
+use crate::sampler;
+use crate::vec3::{cosine_direction, dot, random_unit_vector, unit_vector, Point3, Vec3};
+use std::f64::consts::PI;
+
+/// Describes a probability distribution of directions, over solid angle, that can be
+/// both sampled and evaluated. Pdfs are built for a single hit point and dropped once
+/// it is shaded, so they need not be shared between threads.
+pub trait Pdf {
+    /// Returns the probability density of `generate` choosing the direction.
+    fn value(&self, direction: &Vec3) -> f64;
+
+    /// Returns a random direction drawn from the distribution.
+    fn generate(&self) -> Vec3;
+}
+
+/// Describes the distribution of directions over a hemisphere with density
+/// proportional to the cosine of their angle from its axis, cos(theta) / pi.
+/// Directions are generated from the next two dimensions of the current sample.
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+/// Describes the distribution of directions toward a Hittable from a point, as
+/// sampled by the Hittable's `random` method.
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Point3,
+}
+
+/// Describes an even mixture of two distributions: each generated direction comes
+/// from one of them, chosen at random.
+pub struct MixturePdf<'a> {
+    p: [&'a dyn Pdf; 2],
+}
+
+/// Describes the uniform distribution of directions over the whole sphere.
+pub struct UniformSpherePdf;
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cos_theta = dot(&unit_vector(direction), &self.uvw.w);
+        cos_theta.max(0.0) / PI
+    }
+
+    fn generate(&self) -> Vec3 {
+        let (r1, r2) = sampler::sample_2d();
+        self.uvw.local(&cosine_direction(r1, r2))
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.objects.random(&self.origin)
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_f64() < 0.5 {
+            self.p[0].generate()
+        } else {
+            self.p[1].generate()
+        }
+    }
+}
+
+impl Pdf for UniformSpherePdf {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        random_unit_vector()
+    }
+}
+
+impl CosinePdf {
+    /// Builds a new CosinePdf around the normal w.
+    pub fn build(w: &Vec3) -> Self {
+        Self { uvw: Onb::build(w) }
+    }
+}
+
+impl<'a> HittablePdf<'a> {
+    /// Builds a new HittablePdf of the directions from origin toward objects.
+    pub fn build(objects: &'a dyn Hittable, origin: Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl<'a> MixturePdf<'a> {
+    /// Builds a new MixturePdf drawing half its directions from each of p0 and p1.
+    pub fn build(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self {
+        Self { p: [p0, p1] }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::seed_random;
+
+    /// Estimates the integral of a Pdf over the sphere by sampling it uniformly.
+    fn integrate(pdf: &dyn Pdf) -> f64 {
+        let n = 100_000;
+        let sum: f64 = (0..n).map(|_| pdf.value(&random_unit_vector())).sum();
+        sum / n as f64 * 4.0 * PI
+    }
+
+    #[test]
+    fn test_pdfs() {
+        seed_random(1);
+        let cosine = CosinePdf::build(&Vec3::build(0.0, 2.0, 0.0));
+        let sphere = UniformSpherePdf;
+        let mixture = MixturePdf::build(&cosine, &sphere);
+        for pdf in [&cosine as &dyn Pdf, &sphere, &mixture] {
+            assert!((integrate(pdf) - 1.0).abs() < 0.02);
+        }
+
+        // Cosine-weighted directions all leave the upper hemisphere, and average
+        // cos(theta) = 2/3.
+        let n = 100_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let direction = cosine.generate();
+            assert!(direction.y() >= 0.0);
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            sum += direction.y();
+        }
+        assert!((sum / n as f64 - 2.0 / 3.0).abs() < 0.01);
+        assert_eq!(cosine.value(&Vec3::build(0.0, -1.0, 0.0)), 0.0);
+    }
+}
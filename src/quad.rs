@@ -0,0 +1,207 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::random_f64;
+use crate::ray::Ray;
+use crate::vec3::{cross, dot, unit_vector, Point3, Vec3};
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Describes a Hittable parallelogram: the corner q and the two edges u and v leaving
+/// it. The front face is the side that u × v points toward.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,      // n / (n · n), used to find planar coordinates of hit points
+    normal: Vec3, // Unit normal of the plane holding the Quad
+    d: f64,       // Plane constant, such that normal · p = d on the plane
+    area: f64,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        let denom = dot(&self.normal, &r.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        // No hit if the hit point parameter t is outside the ray interval.
+        let t = (self.d - dot(&self.normal, &r.orig)) / denom;
+        if !ray_t.contains(&t) {
+            return false;
+        }
+
+        // Determine if the hit point lies within the Quad using its planar coordinates.
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(&self.w, &cross(&planar_hitpt_vector, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar_hitpt_vector));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = Arc::clone(&self.mat);
+        rec.set_face_normal(r, &self.normal);
+        rec.object_id = 0;
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    /// Returns the density of `random` choosing the direction: points are picked
+    /// uniformly over the area, which seen from origin covers a solid angle shrinking
+    /// with distance and with the slant of the Quad.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::build(*origin, *direction),
+            0.001..=f64::INFINITY,
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(direction, &rec.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let p = self.q + random_f64() * self.u + random_f64() * self.v;
+        p - *origin
+    }
+}
+
+impl Display for Quad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "quad [{}] + [{}] + [{}] {}",
+            self.q, self.u, self.v, self.mat
+        )
+    }
+}
+
+impl Quad {
+    /// Builds a new Quad from a corner q and the two edges u and v leaving it.
+    pub fn build(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = cross(&u, &v);
+        let normal = unit_vector(&n);
+
+        // Box the diagonals in both directions, padding the box so that it has some
+        // thickness even when the Quad lies in an axis-aligned plane.
+        let bbox = Aabb::build(q, q + u + v)
+            .surrounding(&Aabb::build(q + u, q + v))
+            .padded(0.0001);
+
+        Self {
+            q,
+            u,
+            v,
+            w: n / dot(&n, &n),
+            normal,
+            d: dot(&normal, &q),
+            area: n.length(),
+            mat: material,
+            bbox,
+        }
+    }
+}
+
+/// Returns the six Quads forming the box with opposite corners a and b, with their
+/// front faces outward.
+pub fn make_box(a: Point3, b: Point3, material: Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::new();
+
+    let min = Point3::build(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::build(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::build(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::build(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::build(0.0, 0.0, max.z() - min.z());
+
+    let mat = || Arc::clone(&material);
+    let front = Point3::build(min.x(), min.y(), max.z());
+    let right = Point3::build(max.x(), min.y(), max.z());
+    let back = Point3::build(max.x(), min.y(), min.z());
+    let top = Point3::build(min.x(), max.y(), max.z());
+    sides.add(Quad::build(front, dx, dy, mat())); // front
+    sides.add(Quad::build(right, -dz, dy, mat())); // right
+    sides.add(Quad::build(back, -dx, dy, mat())); // back
+    sides.add(Quad::build(min, dz, dy, mat())); // left
+    sides.add(Quad::build(top, dx, -dz, mat())); // top
+    sides.add(Quad::build(min, dx, dz, mat())); // bottom
+
+    sides
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn test_hit() {
+        let mat = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        let quad = Quad::build(
+            Point3::build(-1.0, -1.0, 0.0),
+            Vec3::build(2.0, 0.0, 0.0),
+            Vec3::build(0.0, 2.0, 0.0),
+            mat,
+        );
+        let mut rec = HitRecord::new();
+
+        let toward = Ray::build(Point3::build(0.5, 0.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(quad.hit(&toward, 0.0..=f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 5.0);
+        assert_eq!((rec.u, rec.v), (0.75, 0.5));
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::build(0.0, 0.0, 1.0));
+
+        let behind = Ray::build(Point3::build(0.0, 0.0, -5.0), Vec3::build(0.0, 0.0, 1.0));
+        assert!(quad.hit(&behind, 0.0..=f64::INFINITY, &mut rec));
+        assert!(!rec.front_face);
+
+        let past = Ray::build(Point3::build(1.5, 0.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(!quad.hit(&past, 0.0..=f64::INFINITY, &mut rec));
+    }
+
+    #[test]
+    fn test_box_faces_outward() {
+        let mat = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        let sides = make_box(
+            Point3::build(1.0, 1.0, 1.0),
+            Point3::build(-1.0, -1.0, -1.0),
+            mat,
+        );
+        assert_eq!(sides.objects.len(), 6);
+
+        let mut rec = HitRecord::new();
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut dir = Vec3::new();
+                dir.e[axis] = sign;
+                let r = Ray::build(-5.0 * dir, dir);
+                assert!(sides.hit(&r, 0.0..=f64::INFINITY, &mut rec));
+                assert_eq!(rec.t, 4.0);
+                assert!(rec.front_face);
+            }
+        }
+    }
+}
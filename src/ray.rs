@@ -1,10 +1,11 @@
 use crate::vec3::{Point3, Vec3};
 
-/// Describes a ray by its origin and direction.
+/// Describes a ray by its origin and direction, and the time at which it is cast.
 #[derive(Clone, Copy, Default)]
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
+    pub tm: f64,
 }
 
 impl Ray {
@@ -13,9 +14,14 @@ impl Ray {
         self.orig + t * self.dir
     }
 
-    /// Builds a new Ray.
+    /// Builds a new Ray cast at time 0.
     pub fn build(orig: Point3, dir: Vec3) -> Self {
-        Self { orig, dir }
+        Self::build_timed(orig, dir, 0.0)
+    }
+
+    /// Builds a new Ray cast at time tm.
+    pub fn build_timed(orig: Point3, dir: Vec3, tm: f64) -> Self {
+        Self { orig, dir, tm }
     }
 
     /// Returns the direction the Ray points.
@@ -27,4 +33,9 @@ impl Ray {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns the time at which the Ray is cast.
+    pub fn time(self) -> f64 {
+        self.tm
+    }
 }
@@ -0,0 +1,569 @@
+use crate::random_f64;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::sync::{Arc, OnceLock};
+
+/// Number of dimensions a Sampler supplies for each sample. Any further dimensions,
+/// such as those of very deep bounces, are drawn at random.
+pub const DIMENSIONS: u32 = 32;
+
+/// The first DIMENSIONS primes, one Halton base for each dimension.
+const PRIMES: [u32; DIMENSIONS as usize] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Width and height of the blue noise mask, which is tiled across the image.
+const MASK_SIZE: usize = 64;
+
+/// Describes a source of sample points: the random numbers that place each sample
+/// within its pixel, on the lens and in the shutter interval, and pick the direction
+/// of its bounces. Each number is one dimension of the sample. Samplers that spread
+/// the samples of a pixel evenly over every dimension reach a given image quality
+/// with far fewer samples than independent random numbers.
+pub trait Sampler: Display + Send + Sync {
+    /// Returns the given dimension of the sample, in the range 0..=1.
+    fn get_1d(&self, sample: &PixelSample, dimension: u32) -> f64;
+
+    /// Returns the given dimension of the sample and the one after it, as a point in
+    /// the unit square.
+    fn get_2d(&self, sample: &PixelSample, dimension: u32) -> (f64, f64) {
+        (
+            self.get_1d(sample, dimension),
+            self.get_1d(sample, dimension + 1),
+        )
+    }
+}
+
+/// Describes one of the samples taken in a pass over a pixel. Later passes continue
+/// the sequence of the earlier ones, so that all the samples of the pixel are spread
+/// evenly together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelSample {
+    pub i: i32,     // Pixel column
+    pub j: i32,     // Pixel row
+    pub seed: u64,  // Random seed for the pixel, the same in every pass
+    pub index: u32, // Position of the sample among all those taken in the pixel
+    pub count: u32, // Number of samples taken in the pass
+}
+
+/// Describes the sample being taken on a thread, and the next of its dimensions to
+/// supply.
+struct SampleStream {
+    sampler: Arc<dyn Sampler>,
+    sample: PixelSample,
+    dimension: u32,
+}
+
+/// Describes a sampler that offsets one shared low-discrepancy sequence by a tiled
+/// blue noise mask, shifted for each dimension. Neighbouring pixels get very
+/// different offsets, so the error left at low sample counts is spread as fine,
+/// even grain rather than clumps.
+pub struct BlueNoiseSampler;
+
+/// Describes a sampler drawing dimension d from the Halton sequence in the d-th prime
+/// base, randomly offset in each pixel.
+pub struct HaltonSampler;
+
+/// Describes a sampler drawing every dimension independently at random.
+pub struct RandomSampler;
+
+/// Describes a sampler drawing pairs of dimensions from the first two dimensions of
+/// the Sobol sequence, shuffled and Owen-scrambled in each pixel.
+pub struct SobolSampler;
+
+/// Describes a sampler splitting each dimension into one stratum for each sample of
+/// the pass, and each pair of dimensions into a grid of cells, jittering every sample
+/// within its own stratum or cell. Each pass fills the strata in a new order.
+pub struct StratifiedSampler;
+
+impl Sampler for BlueNoiseSampler {
+    fn get_1d(&self, sample: &PixelSample, dimension: u32) -> f64 {
+        // The sequence is the same in every pixel; only the offset varies.
+        let x = sobol_1d(sample.index, hash(0, dimension));
+        (x + mask_offset(sample, dimension)).fract()
+    }
+
+    fn get_2d(&self, sample: &PixelSample, dimension: u32) -> (f64, f64) {
+        let (x, y) = sobol_2d(sample.index, hash(0, dimension));
+        (
+            (x + mask_offset(sample, dimension)).fract(),
+            (y + mask_offset(sample, dimension + 1)).fract(),
+        )
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn get_1d(&self, sample: &PixelSample, dimension: u32) -> f64 {
+        let base = PRIMES[(dimension % DIMENSIONS) as usize];
+        let offset = to_unit(hash(sample.seed, dimension) as u32);
+        (radical_inverse(base, sample.index) + offset).fract()
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn get_1d(&self, _sample: &PixelSample, _dimension: u32) -> f64 {
+        random_f64()
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn get_1d(&self, sample: &PixelSample, dimension: u32) -> f64 {
+        sobol_1d(sample.index, hash(sample.seed, dimension))
+    }
+
+    fn get_2d(&self, sample: &PixelSample, dimension: u32) -> (f64, f64) {
+        sobol_2d(sample.index, hash(sample.seed, dimension))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn get_1d(&self, sample: &PixelSample, dimension: u32) -> f64 {
+        let count = sample.count.max(1);
+        let stratum = permute(
+            sample.index % count,
+            count,
+            pass_hash(sample, dimension) as u32,
+        );
+        (stratum as f64 + random_f64()) / count as f64
+    }
+
+    fn get_2d(&self, sample: &PixelSample, dimension: u32) -> (f64, f64) {
+        // The grid may have a few more cells than there are samples; the cells left
+        // empty are picked at random, which keeps the samples evenly spread on average.
+        let count = sample.count.max(1);
+        let nx = (count as f64).sqrt().ceil() as u32;
+        let ny = count.div_ceil(nx);
+        let cell = permute(
+            sample.index % count,
+            nx * ny,
+            pass_hash(sample, dimension) as u32,
+        );
+        (
+            ((cell % nx) as f64 + random_f64()) / nx as f64,
+            ((cell / nx) as f64 + random_f64()) / ny as f64,
+        )
+    }
+}
+
+impl Display for BlueNoiseSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "blue noise")
+    }
+}
+
+impl Display for HaltonSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "halton")
+    }
+}
+
+impl Display for RandomSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "random")
+    }
+}
+
+impl Display for SobolSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sobol")
+    }
+}
+
+impl Display for StratifiedSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "stratified")
+    }
+}
+
+/// The names of the samplers, as accepted by `named`.
+pub const SAMPLER_NAMES: [&str; 5] = ["random", "stratified", "halton", "sobol", "blue_noise"];
+
+/// Returns the sampler with the given name: random (the default), stratified,
+/// halton, sobol or blue_noise.
+pub fn named(name: &str) -> Option<Arc<dyn Sampler>> {
+    match name {
+        "random" => Some(Arc::new(RandomSampler)),
+        "stratified" => Some(Arc::new(StratifiedSampler)),
+        "halton" => Some(Arc::new(HaltonSampler)),
+        "sobol" => Some(Arc::new(SobolSampler)),
+        "blue_noise" => Some(Arc::new(BlueNoiseSampler)),
+        _ => None,
+    }
+}
+
+thread_local! {
+    // Each thread takes its own samples, so that render threads never wait on
+    // each other.
+    static CURRENT: RefCell<Option<SampleStream>> = const { RefCell::new(None) };
+}
+
+/// Starts taking a sample on the calling thread: sample_1d and sample_2d supply its
+/// dimensions in turn, from the first.
+pub fn begin_sample(sampler: &Arc<dyn Sampler>, sample: PixelSample) {
+    let stream = SampleStream {
+        sampler: sampler.clone(),
+        sample,
+        dimension: 0,
+    };
+    CURRENT.with(|current| *current.borrow_mut() = Some(stream));
+}
+
+/// Stops taking samples on the calling thread, so that sample_1d and sample_2d draw
+/// at random again.
+pub fn end_samples() {
+    CURRENT.with(|current| *current.borrow_mut() = None);
+}
+
+/// Returns the next dimension of the sample being taken on the calling thread, or a
+/// random number if there is none or its dimensions have run out.
+pub fn sample_1d() -> f64 {
+    CURRENT.with(|current| match &mut *current.borrow_mut() {
+        Some(stream) if stream.dimension < DIMENSIONS => {
+            stream.dimension += 1;
+            stream.sampler.get_1d(&stream.sample, stream.dimension - 1)
+        }
+        _ => random_f64(),
+    })
+}
+
+/// Returns the next two dimensions of the sample being taken on the calling thread,
+/// as a point in the unit square, or a random point if there is no sample or its
+/// dimensions have run out.
+pub fn sample_2d() -> (f64, f64) {
+    CURRENT.with(|current| match &mut *current.borrow_mut() {
+        Some(stream) if stream.dimension + 2 <= DIMENSIONS => {
+            stream.dimension += 2;
+            stream.sampler.get_2d(&stream.sample, stream.dimension - 2)
+        }
+        _ => (random_f64(), random_f64()),
+    })
+}
+
+/// Returns a random-looking value for a dimension of a seeded sequence, mixing the
+/// two with the SplitMix64 finalizer.
+fn hash(seed: u64, dimension: u32) -> u64 {
+    let mut z = seed ^ (dimension as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns a random-looking value for a dimension of the pass the sample is taken in.
+fn pass_hash(sample: &PixelSample, dimension: u32) -> u64 {
+    let pass = sample.index / sample.count.max(1);
+    hash(sample.seed ^ hash(pass as u64, 0), dimension)
+}
+
+/// Returns the blue noise mask value that offsets the given dimension of the sample.
+/// The mask is shifted by a different amount for each dimension, so that the
+/// dimensions of a pixel are unrelated.
+fn mask_offset(sample: &PixelSample, dimension: u32) -> f64 {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    let mask = MASK.get_or_init(|| {
+        let ranks = void_and_cluster(MASK_SIZE);
+        let n = ranks.len() as f64;
+        ranks
+            .into_iter()
+            .map(|rank| (rank as f64 + 0.5) / n)
+            .collect()
+    });
+
+    let shift = hash(0, dimension);
+    let x = (sample.i as i64 + (shift & 0xffff) as i64).rem_euclid(MASK_SIZE as i64);
+    let y = (sample.j as i64 + (shift >> 16 & 0xffff) as i64).rem_euclid(MASK_SIZE as i64);
+    mask[y as usize * MASK_SIZE + x as usize]
+}
+
+/// Returns the Owen scrambling of x's bits with the given seed: every bit is flipped
+/// or not depending on the bits above it, as Burley's "Practical Hash-based Owen
+/// Scrambling" does with the Laine-Karras permutation.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/// Returns element i of a random permutation of 0..l chosen by the seed p, by
+/// Kensler's hashing from "Correlated Multi-Jittered Sampling".
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            return i.wrapping_add(p) % l;
+        }
+    }
+}
+
+/// Returns the radical inverse of index in the given base: its digits mirrored
+/// about the radix point.
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut digit_value = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * digit_value;
+        index /= base;
+        digit_value *= inv_base;
+    }
+    result
+}
+
+/// Returns point index of a one-dimensional Sobol sequence, shuffled and scrambled
+/// by the seed.
+fn sobol_1d(index: u32, seed: u64) -> f64 {
+    let index = nested_uniform_scramble(index, seed as u32);
+    to_unit(nested_uniform_scramble(
+        index.reverse_bits(),
+        (seed >> 32) as u32,
+    ))
+}
+
+/// Returns point index of a two-dimensional Sobol sequence, shuffled and scrambled
+/// by the seed. Shuffling by Owen scrambling keeps each run of 2^m points from an
+/// aligned start, and so the first 2^m points, stratified over every 2^m-cell grid
+/// of the unit square.
+fn sobol_2d(index: u32, seed: u64) -> (f64, f64) {
+    let index = nested_uniform_scramble(index, seed as u32);
+
+    // The second dimension's generator matrix is Pascal's triangle, mod 2.
+    let x = index.reverse_bits();
+    let mut y = 0;
+    let mut v = 1 << 31;
+    let mut bits = index;
+    while bits != 0 {
+        if bits & 1 != 0 {
+            y ^= v;
+        }
+        bits >>= 1;
+        v ^= v >> 1;
+    }
+
+    let seed = hash(seed, 0);
+    (
+        to_unit(nested_uniform_scramble(x, seed as u32)),
+        to_unit(nested_uniform_scramble(y, (seed >> 32) as u32)),
+    )
+}
+
+/// Returns x as a fraction of 2^32.
+fn to_unit(x: u32) -> f64 {
+    x as f64 / 4_294_967_296.0
+}
+
+/// Returns the rank of each cell of a size by size blue noise mask, which tiles
+/// seamlessly, built by Ulichney's void-and-cluster method. Points are placed one
+/// at a time in the emptiest cell, as measured by a Gaussian blur of the points
+/// placed so far, so that cells of close rank are never close together.
+fn void_and_cluster(size: usize) -> Vec<usize> {
+    const RADIUS: isize = 6;
+    const SIGMA: f64 = 1.5;
+
+    let n = size * size;
+    let splat = |energy: &mut [f64], cell: usize, sign: f64| {
+        let (x, y) = ((cell % size) as isize, (cell / size) as isize);
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                let weight = (-((dx * dx + dy * dy) as f64) / (2.0 * SIGMA * SIGMA)).exp();
+                let cx = (x + dx).rem_euclid(size as isize) as usize;
+                let cy = (y + dy).rem_euclid(size as isize) as usize;
+                energy[cy * size + cx] += sign * weight;
+            }
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f64]| {
+        (0..n)
+            .filter(|&cell| pattern[cell])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f64]| {
+        (0..n)
+            .filter(|&cell| !pattern[cell])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Scatter a tenth of the points at random, then even them out by moving the
+    // point in the tightest cluster to the largest void until that stops changing.
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut cells: Vec<usize> = (0..n).collect();
+    cells.shuffle(&mut rng);
+    let initial = n / 10;
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0; n];
+    for &cell in &cells[..initial] {
+        pattern[cell] = true;
+        splat(&mut energy, cell, 1.0);
+    }
+    for _ in 0..n {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    // Rank the initial points by taking them away, tightest cluster first, then the
+    // rest of the cells by filling them, largest void first.
+    let mut ranks = vec![0; n];
+    let (mut remaining, mut remaining_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&remaining, &remaining_energy);
+        remaining[cluster] = false;
+        splat(&mut remaining_energy, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+    for rank in initial..n {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        ranks[void] = rank;
+    }
+    ranks
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pixel_sample(index: u32, count: u32) -> PixelSample {
+        PixelSample {
+            i: 3,
+            j: 5,
+            seed: 42,
+            index,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_samplers() {
+        for name in SAMPLER_NAMES {
+            let sampler = named(name).unwrap();
+            for index in 0..64 {
+                for dimension in 0..DIMENSIONS {
+                    let x = sampler.get_1d(&pixel_sample(index, 64), dimension);
+                    assert!((0.0..=1.0).contains(&x), "{}", name);
+                }
+            }
+        }
+        assert!(named("latin_hypercube").is_none());
+
+        // Every cell of a 4x4 grid gets one of 16 samples.
+        for sampler in [&StratifiedSampler as &dyn Sampler, &SobolSampler] {
+            for dimension in [0, 2, 7] {
+                let mut cells = [0; 16];
+                for index in 0..16 {
+                    let (x, y) = sampler.get_2d(&pixel_sample(index, 16), dimension);
+                    cells[(y * 4.0) as usize * 4 + (x * 4.0) as usize] += 1;
+                }
+                assert_eq!(cells, [1; 16], "{} {}", sampler, dimension);
+            }
+        }
+
+        // Every sixteenth of the first dimension gets one of 16 samples.
+        for sampler in [
+            &StratifiedSampler as &dyn Sampler,
+            &HaltonSampler,
+            &SobolSampler,
+        ] {
+            let mut strata = [0; 16];
+            for index in 0..16 {
+                strata[(sampler.get_1d(&pixel_sample(index, 16), 0) * 16.0) as usize] += 1;
+            }
+            assert_eq!(strata, [1; 16], "{}", sampler);
+        }
+
+        // A second pass of 8 samples fills the strata the first pass left empty.
+        for sampler in [
+            &HaltonSampler as &dyn Sampler,
+            &SobolSampler,
+            &BlueNoiseSampler,
+        ] {
+            let mut strata = [0; 16];
+            for index in 0..16 {
+                strata[(sampler.get_1d(&pixel_sample(index, 8), 0) * 16.0) as usize] += 1;
+            }
+            assert_eq!(strata, [1; 16], "{}", sampler);
+        }
+    }
+
+    #[test]
+    fn test_sample_stream() {
+        let sampler = named("sobol").unwrap();
+        let sample = pixel_sample(5, 16);
+        begin_sample(&sampler, sample);
+        assert_eq!(sample_2d(), sampler.get_2d(&sample, 0));
+        assert_eq!(sample_1d(), sampler.get_1d(&sample, 2));
+        for _ in 3..DIMENSIONS {
+            sample_1d();
+        }
+
+        // Past the last dimension, and after the sample ends, numbers are random.
+        crate::seed_random(7);
+        let x = sample_1d();
+        end_samples();
+        crate::seed_random(7);
+        assert_eq!(x, random_f64());
+        crate::seed_random(7);
+        let x = sample_1d();
+        crate::seed_random(7);
+        assert_eq!(x, random_f64());
+    }
+
+    #[test]
+    fn test_blue_noise_mask() {
+        let ranks = void_and_cluster(MASK_SIZE);
+        let mut sorted = ranks.clone();
+        sorted.sort();
+        assert!(sorted.into_iter().eq(0..MASK_SIZE * MASK_SIZE));
+
+        // Blue noise has no low frequencies, so neighbouring cells differ by much more
+        // than the third of the range that white noise averages.
+        let n = ranks.len() as f64;
+        let difference: f64 = (0..ranks.len())
+            .map(|cell| {
+                let right = cell / MASK_SIZE * MASK_SIZE + (cell + 1) % MASK_SIZE;
+                (ranks[cell] as f64 - ranks[right] as f64).abs() / n
+            })
+            .sum();
+        assert!(difference / n > 0.4, "{}", difference / n);
+    }
+}
@@ -0,0 +1,929 @@
+//! Loads scenes from JSON description files.
+//!
+//! A scene file holds an optional `camera` object whose members override the Camera
+//! defaults, an optional `background`, height `fog`, `integrator` (path, naive, ao or
+//! whitted) and `sampler` (random, stratified, halton, sobol or blue_noise), a
+//! `textures` object mapping names to texture descriptions, a `materials` object
+//! mapping names to material descriptions, and an `objects` array of shapes that
+//! refer to those materials by name. Any object can be placed by an optional
+//! `transform`, and set moving by a `motion` offset reached at time 1, which blurs it
+//! while the camera shutter is open. Emitters marked `"light": true` are also sampled
+//! directly by shadow rays; only spheres, quads and boxes without a transform or
+//! motion can be. A material's `albedo` or `emit` is either a color or the name of a
+//! texture:
+//!
+//! ```json
+//! {
+//!     "camera": { "image_width": 400, "look_from": [13, 2, 3], "vfov": 20, "shutter_close": 1 },
+//!     "background": { "type": "environment", "file": "studio.hdr", "rotation": 90 },
+//!     "fog": { "density": 0.05, "falloff": 0.5, "height": 0, "color": [0.8, 0.85, 0.9] },
+//!     "integrator": { "type": "ao", "distance": 0.5 },
+//!     "sampler": { "type": "sobol" },
+//!     "textures": {
+//!         "checks": { "type": "checker", "scale": 0.32, "even": [0.2, 0.3, 0.1], "odd": [0.9, 0.9, 0.9] },
+//!         "earth": { "type": "image", "file": "earthmap.png" },
+//!         "stone": { "type": "marble", "scale": 4, "seed": 2 }
+//!     },
+//!     "materials": {
+//!         "ground": { "type": "lambertian", "albedo": "checks" },
+//!         "glass": { "type": "dielectric", "ir": 1.5 },
+//!         "steel": { "type": "metal", "albedo": [0.7, 0.6, 0.5], "fuzz": 0.1 },
+//!         "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] },
+//!         "smoke": { "type": "isotropic", "albedo": [1, 1, 1] }
+//!     },
+//!     "objects": [
+//!         { "type": "sphere", "center": [0, -1000, 0], "radius": 1000, "material": "ground" },
+//!         { "type": "sphere", "center": [0, 1, 0], "center2": [0, 1.5, 0], "radius": 1, "material": "lamp",
+//!           "light": true },
+//!         { "type": "quad", "q": [-2, 0, -3], "u": [4, 0, 0], "v": [0, 4, 0], "material": "steel" },
+//!         { "type": "box", "a": [1, 0, 1], "b": [2, 1, 2], "material": "glass" },
+//!         { "type": "obj", "file": "teapot.obj" },
+//!         { "type": "constant_medium", "density": 0.2, "material": "smoke",
+//!           "boundary": { "type": "sphere", "center": [0, 1, 4], "radius": 1 } },
+//!         { "type": "heterogeneous_medium", "material": "smoke",
+//!           "density": { "type": "noise", "scale": 2, "max": 4 },
+//!           "boundary": { "type": "box", "a": [-4, 0, -4], "b": [-2, 2, -2] } },
+//!         { "type": "obj", "file": "teapot.obj", "transform": [
+//!             { "scale": 0.5 }, { "rotate": [0, 1, 0], "angle": 30 }, { "translate": [3, 0, 0] }
+//!         ] }
+//!     ]
+//! }
+//! ```
+
+use crate::background::{Background, EnvironmentMap, GradientBackground, SolidBackground};
+use crate::bvh::{BvhNode, SplitHeuristic};
+use crate::camera::Camera;
+use crate::constant_medium::ConstantMedium;
+use crate::hittable::{Hittable, Tagged};
+use crate::hittable_list::HittableList;
+use crate::image_io;
+use crate::integrator::{self, AmbientOcclusionIntegrator, Integrator};
+use crate::json::{self, Value};
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::obj;
+use crate::quad::{make_box, Quad};
+use crate::sampler::{self, Sampler};
+use crate::sphere::Sphere;
+use crate::texture::{
+    CheckerTexture, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture,
+    TurbulenceTexture, UvCheckerTexture, WoodTexture, WorleyTexture,
+};
+use crate::transform::{Instance, Transform};
+use crate::triangle::Triangle;
+use crate::vec3::{Color, Vec3};
+use crate::volume::{DensityField, GridDensity, HeightFog, HeterogeneousMedium, NoiseDensity};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Describes a renderable scene: the objects in the world, the camera viewing them,
+/// and the lights among them to sample directly. Each object in the world is Tagged
+/// with its position in the list, counting from 1.
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList,
+    pub lights: HittableList,
+}
+
+/// Describes why a scene could not be loaded.
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(json::ParseError),
+    Invalid(String), // The document is valid JSON but not a valid scene
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "{}", err),
+            SceneError::Parse(err) => write!(f, "{}", err),
+            SceneError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(err: io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+impl From<json::ParseError> for SceneError {
+    fn from(err: json::ParseError) -> Self {
+        SceneError::Parse(err)
+    }
+}
+
+impl Scene {
+    /// Builds a Scene viewing the world through a default-initialized Camera, with no
+    /// lights to sample directly.
+    pub fn build(world: HittableList) -> Self {
+        Self {
+            camera: Camera::new(),
+            world: tag_objects(world),
+            lights: HittableList::new(),
+        }
+    }
+
+    /// Loads a Scene from a JSON scene file. Files the scene refers to are found
+    /// relative to the directory holding the scene file.
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        let text = std::fs::read_to_string(path)?;
+        Scene::parse_in(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Builds a Scene from the text of a JSON scene description. Files the scene
+    /// refers to are found relative to the current directory.
+    pub fn parse(text: &str) -> Result<Scene, SceneError> {
+        Scene::parse_in(text, Path::new(""))
+    }
+
+    /// Builds a Scene from the text of a JSON scene description, finding the files it
+    /// refers to relative to base_dir.
+    pub fn parse_in(text: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let doc = json::parse(text)?;
+
+        let mut camera = Camera::new();
+        if let Some(settings) = doc.get("camera") {
+            parse_camera(settings, &mut camera)?;
+        }
+        if let Some(description) = doc.get("background") {
+            camera.background = parse_background(description, base_dir)
+                .map_err(|err| invalid(&format!("background: {}", err)))?;
+        }
+        if let Some(description) = doc.get("integrator") {
+            camera.integrator = parse_integrator(description)
+                .map_err(|err| invalid(&format!("integrator: {}", err)))?;
+        }
+        if let Some(description) = doc.get("sampler") {
+            camera.sampler =
+                parse_sampler(description).map_err(|err| invalid(&format!("sampler: {}", err)))?;
+        }
+        if let Some(description) = doc.get("fog") {
+            camera.fog =
+                Some(parse_fog(description).map_err(|err| invalid(&format!("fog: {}", err)))?);
+        }
+
+        let mut textures = HashMap::new();
+        if let Some(entries) = doc.get("textures") {
+            let entries = entries
+                .as_object()
+                .ok_or_else(|| invalid("'textures' must be an object"))?;
+            for (name, description) in entries {
+                let tex = parse_texture(description, base_dir)
+                    .map_err(|err| invalid(&format!("texture '{}': {}", name, err)))?;
+                textures.insert(name.clone(), tex);
+            }
+        }
+
+        let mut materials = HashMap::new();
+        if let Some(entries) = doc.get("materials") {
+            let entries = entries
+                .as_object()
+                .ok_or_else(|| invalid("'materials' must be an object"))?;
+            for (name, description) in entries {
+                let mat = parse_material(description, &textures)
+                    .map_err(|err| invalid(&format!("material '{}': {}", name, err)))?;
+                materials.insert(name.clone(), mat);
+            }
+        }
+
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+        let mut meshes = HashMap::new();
+        if let Some(objects) = doc.get("objects") {
+            let objects = objects
+                .as_array()
+                .ok_or_else(|| invalid("'objects' must be an array"))?;
+            for (i, description) in objects.iter().enumerate() {
+                let in_object = |err: SceneError| invalid(&format!("object {}: {}", i, err));
+                let first = world.objects.len();
+                parse_object(description, &materials, base_dir, &mut meshes, &mut world)
+                    .map_err(in_object)?;
+                if optional_bool(description, "light")
+                    .map_err(in_object)?
+                    .unwrap_or(false)
+                {
+                    check_light(description).map_err(in_object)?;
+                    lights.objects.extend_from_slice(&world.objects[first..]);
+                }
+            }
+        }
+
+        Ok(Scene {
+            camera,
+            world: tag_objects(world),
+            lights,
+        })
+    }
+}
+
+/// Checks that an `objects` entry marked as a light can be sampled by shadow rays:
+/// only spheres, quads and boxes can, and only where the scene puts them directly,
+/// without a transform or motion.
+fn check_light(description: &Value) -> Result<(), SceneError> {
+    let object_type = required_str(description, "type")?;
+    let placed = description.get("transform").is_some() || description.get("motion").is_some();
+    if !matches!(object_type, "sphere" | "quad" | "box") || placed {
+        return Err(invalid(&format!(
+            "a {} {}cannot be a light",
+            object_type,
+            if placed {
+                "with a transform or motion "
+            } else {
+                ""
+            }
+        )));
+    }
+    Ok(())
+}
+
+/// Applies the camera settings present in a scene file to the Camera.
+fn parse_camera(settings: &Value, camera: &mut Camera) -> Result<(), SceneError> {
+    if settings.as_object().is_none() {
+        return Err(invalid("'camera' must be an object"));
+    }
+    let number = |key| optional_f64(settings, key);
+    let count = |key| optional_integer(settings, key, 1);
+    let vector = |key| optional_vec3(settings, key);
+
+    if let Some(x) = number("aspect_ratio")? {
+        camera.aspect_ratio = x;
+    }
+    if let Some(x) = count("image_width")? {
+        camera.image_width = x;
+    }
+    if let Some(x) = count("samples_per_pixel")? {
+        camera.samples_per_pixel = x;
+    }
+    if let Some(x) = number("adaptive_threshold")? {
+        camera.adaptive_threshold = x;
+    }
+    if let Some(x) = count("min_samples_per_pixel")? {
+        camera.min_samples_per_pixel = x;
+    }
+    if let Some(x) = count("max_samples_per_pixel")? {
+        camera.max_samples_per_pixel = x;
+    }
+    if let Some(x) = count("max_depth")? {
+        camera.max_depth = x;
+    }
+    if let Some(x) = optional_integer(settings, "roulette_depth", 0)? {
+        camera.roulette_depth = x;
+    }
+    if let Some(x) = number("vfov")? {
+        camera.vfov = x;
+    }
+    if let Some(v) = vector("look_from")? {
+        camera.look_from = v;
+    }
+    if let Some(v) = vector("look_at")? {
+        camera.look_at = v;
+    }
+    if let Some(v) = vector("vup")? {
+        camera.vup = v;
+    }
+    if let Some(x) = number("defocus_angle")? {
+        camera.defocus_angle = x;
+    }
+    if let Some(x) = number("focus_dist")? {
+        camera.focus_dist = x;
+    }
+    if let Some(x) = number("seed")? {
+        camera.seed = x as u64;
+    }
+    if let Some(x) = number("shutter_open")? {
+        camera.shutter_open = x;
+    }
+    if let Some(x) = number("shutter_close")? {
+        camera.shutter_close = x;
+    }
+    Ok(())
+}
+
+/// Builds the background described by the `background` member.
+fn parse_background(
+    description: &Value,
+    base_dir: &Path,
+) -> Result<Arc<dyn Background>, SceneError> {
+    match required_str(description, "type")? {
+        "solid" => Ok(Arc::new(SolidBackground {
+            color: required_vec3(description, "color")?,
+        })),
+        "gradient" => Ok(Arc::new(GradientBackground::build(
+            required_vec3(description, "bottom")?,
+            required_vec3(description, "top")?,
+        ))),
+        "environment" => {
+            let path = base_dir.join(required_str(description, "file")?);
+            let image = image_io::read_image(&path)
+                .map_err(|err| invalid(&format!("{}: {}", path.display(), err)))?;
+            Ok(Arc::new(EnvironmentMap::build(
+                image,
+                optional_f64(description, "rotation")?.unwrap_or(0.0),
+                optional_f64(description, "intensity")?.unwrap_or(1.0),
+            )))
+        }
+        other => Err(invalid(&format!("unknown background type '{}'", other))),
+    }
+}
+
+/// Builds the texture described by a `textures` entry. The two textures of a checker
+/// are each either a color or a nested texture description. Procedural textures take
+/// an optional `seed` for their noise, so that several can differ.
+fn parse_texture(description: &Value, base_dir: &Path) -> Result<Arc<dyn Texture>, SceneError> {
+    let seed = optional_f64(description, "seed")?.unwrap_or(0.0) as u64;
+    let scale = optional_f64(description, "scale")?.unwrap_or(1.0);
+    let color = |key, default: Color| optional_vec3(description, key).map(|c| c.unwrap_or(default));
+    let white = Color::build(1.0, 1.0, 1.0);
+
+    let check = |key| match description.get(key) {
+        Some(nested) if nested.as_object().is_some() => parse_texture(nested, base_dir),
+        _ => Ok(Arc::new(SolidColor::from(required_vec3(description, key)?)) as Arc<dyn Texture>),
+    };
+
+    match required_str(description, "type")? {
+        "solid" => Ok(Arc::new(SolidColor::from(required_vec3(
+            description,
+            "color",
+        )?))),
+        "checker" => Ok(Arc::new(CheckerTexture::build(
+            scale,
+            check("even")?,
+            check("odd")?,
+        ))),
+        "uv_checker" => Ok(Arc::new(UvCheckerTexture::build(
+            optional_f64(description, "columns")?.unwrap_or(16.0),
+            optional_f64(description, "rows")?.unwrap_or(8.0),
+            check("even")?,
+            check("odd")?,
+        ))),
+        "image" => {
+            let path = base_dir.join(required_str(description, "file")?);
+            let image = image_io::read_image(&path)
+                .map_err(|err| invalid(&format!("{}: {}", path.display(), err)))?;
+            Ok(Arc::new(ImageTexture::build(image)))
+        }
+        "noise" => Ok(Arc::new(NoiseTexture::build(
+            seed,
+            scale,
+            color("color", white)?,
+        ))),
+        "turbulence" => Ok(Arc::new(TurbulenceTexture::build(
+            seed,
+            scale,
+            optional_f64(description, "depth")?.unwrap_or(7.0) as i32,
+            color("color", white)?,
+        ))),
+        "marble" => Ok(Arc::new(MarbleTexture::build(
+            seed,
+            scale,
+            optional_f64(description, "turbulence")?.unwrap_or(10.0),
+            color("base", white)?,
+            color("vein", Color::new())?,
+        ))),
+        "wood" => Ok(Arc::new(WoodTexture::build(
+            seed,
+            scale,
+            optional_f64(description, "turbulence")?.unwrap_or(0.5),
+            color("light", Color::build(0.76, 0.6, 0.42))?,
+            color("dark", Color::build(0.4, 0.26, 0.13))?,
+        ))),
+        "worley" => Ok(Arc::new(WorleyTexture::build(
+            seed,
+            scale,
+            color("near", white)?,
+            color("far", Color::new())?,
+        ))),
+        other => Err(invalid(&format!("unknown texture type '{}'", other))),
+    }
+}
+
+/// Builds the material described by a `materials` entry.
+fn parse_material(
+    description: &Value,
+    textures: &HashMap<String, Arc<dyn Texture>>,
+) -> Result<Arc<dyn Material>, SceneError> {
+    match required_str(description, "type")? {
+        "lambertian" => Ok(Arc::new(Lambertian::from_texture(required_texture(
+            description,
+            "albedo",
+            textures,
+        )?))),
+        "metal" => Ok(Arc::new(Metal::from_texture(
+            required_texture(description, "albedo", textures)?,
+            optional_f64(description, "fuzz")?.unwrap_or(0.0),
+        ))),
+        "dielectric" => Ok(Arc::new(Dielectric::build(required_f64(
+            description,
+            "ir",
+        )?))),
+        "diffuse_light" => Ok(Arc::new(DiffuseLight::from_texture(required_texture(
+            description,
+            "emit",
+            textures,
+        )?))),
+        "isotropic" => Ok(Arc::new(Isotropic::from_texture(required_texture(
+            description,
+            "albedo",
+            textures,
+        )?))),
+        other => Err(invalid(&format!("unknown material type '{}'", other))),
+    }
+}
+
+/// Builds the shape described by an `objects` entry and adds it to the world.
+/// Meshes loaded from OBJ files use their own MTL materials; the `material` member,
+/// optional for them, covers faces without one. Each OBJ file is loaded once, into
+/// the meshes cache, however many objects place it.
+fn parse_object(
+    description: &Value,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    base_dir: &Path,
+    meshes: &mut HashMap<(PathBuf, Option<String>), Arc<dyn Hittable>>,
+    world: &mut HittableList,
+) -> Result<(), SceneError> {
+    let object_type = required_str(description, "type")?;
+    let mat_name = match (object_type, description.get("material")) {
+        ("obj", None) => None,
+        _ => Some(required_str(description, "material")?),
+    };
+    let mat = match mat_name {
+        None => Arc::new(Lambertian::build(0.5, 0.5, 0.5)),
+        Some(mat_name) => materials
+            .get(mat_name)
+            .ok_or_else(|| invalid(&format!("unknown material '{}'", mat_name)))?
+            .clone(),
+    };
+
+    let mut parts = HittableList::new();
+    match object_type {
+        "sphere" => {
+            let center = required_vec3(description, "center")?;
+            parts.add(Sphere::moving(
+                center,
+                optional_vec3(description, "center2")?.unwrap_or(center),
+                required_f64(description, "radius")?,
+                mat,
+            ))
+        }
+        "quad" => parts.add(Quad::build(
+            required_vec3(description, "q")?,
+            required_vec3(description, "u")?,
+            required_vec3(description, "v")?,
+            mat,
+        )),
+        "triangle" => parts.add(Triangle::build(
+            required_vec3(description, "a")?,
+            required_vec3(description, "b")?,
+            required_vec3(description, "c")?,
+            mat,
+        )),
+        "box" => parts.add(make_box(
+            required_vec3(description, "a")?,
+            required_vec3(description, "b")?,
+            mat,
+        )),
+        "obj" => {
+            let path = base_dir.join(required_str(description, "file")?);
+            let key = (path.clone(), mat_name.map(str::to_string));
+            let mesh = match meshes.get(&key) {
+                Some(mesh) => mesh.clone(),
+                None => {
+                    let triangles = obj::load_obj(&path, mat)
+                        .map_err(|err| invalid(&format!("{}: {}", path.display(), err)))?;
+                    let mesh: Arc<dyn Hittable> =
+                        Arc::new(BvhNode::build(triangles, SplitHeuristic::Sah));
+                    meshes.insert(key, mesh.clone());
+                    mesh
+                }
+            };
+            parts.objects.push(mesh);
+        }
+        "constant_medium" => parts.add(ConstantMedium::from(
+            parse_boundary(description, materials, base_dir, meshes)?,
+            required_f64(description, "density")?,
+            mat,
+        )),
+        "heterogeneous_medium" => {
+            let field = description
+                .get("density")
+                .ok_or_else(|| invalid("missing 'density'"))?;
+            let field = parse_density(field, base_dir)
+                .map_err(|err| invalid(&format!("density: {}", err)))?;
+            parts.add(HeterogeneousMedium::build(
+                parse_boundary(description, materials, base_dir, meshes)?,
+                field,
+                mat,
+            ))
+        }
+        other => return Err(invalid(&format!("unknown object type '{}'", other))),
+    }
+
+    let motion = optional_vec3(description, "motion")?;
+    match (description.get("transform"), motion) {
+        (None, None) => world.objects.extend(parts.objects),
+        (steps, motion) => {
+            let transform = match steps {
+                Some(steps) => parse_transform(steps)?,
+                None => Transform::new(),
+            };
+            let object = parts.objects.pop().unwrap();
+            let instance = Instance::moving(object, transform, motion.unwrap_or_default())
+                .ok_or_else(|| invalid("'transform' cannot be inverted"))?;
+            world.add(instance);
+        }
+    }
+    Ok(())
+}
+
+/// Builds the boundary shape of a medium described by an `objects` entry. The
+/// boundary is only a shape, so it needs no material of its own.
+fn parse_boundary(
+    description: &Value,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    base_dir: &Path,
+    meshes: &mut HashMap<(PathBuf, Option<String>), Arc<dyn Hittable>>,
+) -> Result<Arc<dyn Hittable>, SceneError> {
+    let mut boundary = description
+        .get("boundary")
+        .cloned()
+        .ok_or_else(|| invalid("missing 'boundary'"))?;
+    if let (Value::Object(members), Some(mat_name)) = (&mut boundary, description.get("material")) {
+        members
+            .entry("material".to_string())
+            .or_insert_with(|| mat_name.clone());
+    }
+
+    let mut shape = HittableList::new();
+    parse_object(&boundary, materials, base_dir, meshes, &mut shape)
+        .map_err(|err| invalid(&format!("boundary: {}", err)))?;
+    Ok(match shape.objects.len() {
+        1 => shape.objects.pop().unwrap(),
+        _ => Arc::new(shape),
+    })
+}
+
+/// Builds the density field described by the `density` member of a heterogeneous
+/// medium: Perlin turbulence, or a grid loaded from a `.vol` file.
+fn parse_density(
+    description: &Value,
+    base_dir: &Path,
+) -> Result<Arc<dyn DensityField>, SceneError> {
+    match required_str(description, "type")? {
+        "noise" => Ok(Arc::new(NoiseDensity::build(
+            optional_f64(description, "seed")?.unwrap_or(0.0) as u64,
+            optional_f64(description, "scale")?.unwrap_or(1.0),
+            optional_f64(description, "depth")?.unwrap_or(7.0) as i32,
+            required_f64(description, "max")?,
+        ))),
+        "grid" => {
+            let path = base_dir.join(required_str(description, "file")?);
+            let grid = GridDensity::load(&path)
+                .map_err(|err| invalid(&format!("{}: {}", path.display(), err)))?;
+            Ok(Arc::new(grid))
+        }
+        other => Err(invalid(&format!("unknown density type '{}'", other))),
+    }
+}
+
+/// Builds the integrator described by the `integrator` member.
+fn parse_integrator(description: &Value) -> Result<Arc<dyn Integrator>, SceneError> {
+    match required_str(description, "type")? {
+        "ao" => Ok(Arc::new(AmbientOcclusionIntegrator::build(
+            optional_f64(description, "distance")?.unwrap_or(1.0),
+        ))),
+        name => integrator::named(name)
+            .ok_or_else(|| invalid(&format!("unknown integrator type '{}'", name))),
+    }
+}
+
+/// Builds the sampler described by the `sampler` member.
+fn parse_sampler(description: &Value) -> Result<Arc<dyn Sampler>, SceneError> {
+    let name = required_str(description, "type")?;
+    sampler::named(name).ok_or_else(|| invalid(&format!("unknown sampler type '{}'", name)))
+}
+
+/// Returns the objects of the world, each Tagged with its position in the list
+/// counting from 1.
+fn tag_objects(world: HittableList) -> HittableList {
+    let mut tagged = HittableList::new();
+    for (i, object) in world.objects.into_iter().enumerate() {
+        tagged.add(Tagged::build(object, i as u32 + 1));
+    }
+    tagged
+}
+
+/// Builds the HeightFog described by the `fog` member.
+fn parse_fog(description: &Value) -> Result<HeightFog, SceneError> {
+    Ok(HeightFog::build(
+        required_f64(description, "density")?,
+        optional_f64(description, "falloff")?.unwrap_or(1.0),
+        optional_f64(description, "height")?.unwrap_or(0.0),
+        optional_vec3(description, "color")?.unwrap_or(Color::build(1.0, 1.0, 1.0)),
+    ))
+}
+
+/// Builds the Transform described by an object's `transform` member: an array of
+/// steps, applied in order, each one of `{ "scale": [x, y, z] }` (or a single
+/// number), `{ "rotate": [x, y, z], "angle": degrees }` or `{ "translate": [x, y, z] }`.
+fn parse_transform(steps: &Value) -> Result<Transform, SceneError> {
+    let steps = steps
+        .as_array()
+        .ok_or_else(|| invalid("'transform' must be an array"))?;
+
+    let mut transform = Transform::new();
+    for step in steps {
+        let next = if let Some(factor) = step.get("scale").and_then(Value::as_f64) {
+            Transform::scale(&Vec3::build(factor, factor, factor))
+        } else if step.get("scale").is_some() {
+            Transform::scale(&required_vec3(step, "scale")?)
+        } else if step.get("rotate").is_some() {
+            Transform::rotate(
+                &required_vec3(step, "rotate")?,
+                required_f64(step, "angle")?,
+            )
+        } else if step.get("translate").is_some() {
+            Transform::translate(&required_vec3(step, "translate")?)
+        } else {
+            return Err(invalid(
+                "transform steps must be 'scale', 'rotate' or 'translate'",
+            ));
+        };
+        transform = transform.then(&next);
+    }
+    Ok(transform)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Value helpers
+
+fn invalid(message: &str) -> SceneError {
+    SceneError::Invalid(message.to_string())
+}
+
+fn optional_bool(value: &Value, key: &str) -> Result<Option<bool>, SceneError> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| invalid(&format!("'{}' must be true or false", key))),
+    }
+}
+
+fn optional_f64(value: &Value, key: &str) -> Result<Option<f64>, SceneError> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| invalid(&format!("'{}' must be a number", key))),
+    }
+}
+
+/// Returns an integer member, which must be at least min.
+fn optional_integer(value: &Value, key: &str, min: i32) -> Result<Option<i32>, SceneError> {
+    let Some(x) = optional_f64(value, key)? else {
+        return Ok(None);
+    };
+    if x.fract() != 0.0 || x < min as f64 || x > i32::MAX as f64 {
+        return Err(invalid(&format!(
+            "'{}' must be an integer of at least {}",
+            key, min
+        )));
+    }
+    Ok(Some(x as i32))
+}
+
+fn optional_vec3(value: &Value, key: &str) -> Result<Option<Vec3>, SceneError> {
+    let Some(v) = value.get(key) else {
+        return Ok(None);
+    };
+    let components: Option<Vec<f64>> = v
+        .as_array()
+        .filter(|e| e.len() == 3)
+        .and_then(|e| e.iter().map(Value::as_f64).collect());
+    match components {
+        Some(e) => Ok(Some(Vec3::build(e[0], e[1], e[2]))),
+        None => Err(invalid(&format!("'{}' must be an array of 3 numbers", key))),
+    }
+}
+
+fn required_f64(value: &Value, key: &str) -> Result<f64, SceneError> {
+    optional_f64(value, key)?.ok_or_else(|| invalid(&format!("missing '{}'", key)))
+}
+
+fn required_str<'a>(value: &'a Value, key: &str) -> Result<&'a str, SceneError> {
+    match value.get(key) {
+        None => Err(invalid(&format!("missing '{}'", key))),
+        Some(v) => v
+            .as_str()
+            .ok_or_else(|| invalid(&format!("'{}' must be a string", key))),
+    }
+}
+
+/// Returns the texture named by a string member, or a solid texture for a color member.
+fn required_texture(
+    value: &Value,
+    key: &str,
+    textures: &HashMap<String, Arc<dyn Texture>>,
+) -> Result<Arc<dyn Texture>, SceneError> {
+    match value.get(key).and_then(Value::as_str) {
+        Some(name) => textures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| invalid(&format!("unknown texture '{}'", name))),
+        None => Ok(Arc::new(SolidColor::from(required_vec3(value, key)?))),
+    }
+}
+
+fn required_vec3(value: &Value, key: &str) -> Result<Vec3, SceneError> {
+    optional_vec3(value, key)?.ok_or_else(|| invalid(&format!("missing '{}'", key)))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_example() {
+        let scene = Scene::parse(include_str!("../scenes/hollow_glass.json")).unwrap();
+        assert_eq!(scene.world.objects.len(), 5);
+        assert_eq!(scene.camera.image_width, 400);
+    }
+
+    #[test]
+    fn test_camera_integers() {
+        let scene = Scene::parse(r#"{ "camera": { "image_width": 64, "roulette_depth": 0 } }"#);
+        let camera = scene.unwrap().camera;
+        assert_eq!((camera.image_width, camera.roulette_depth), (64, 0));
+
+        for (settings, message) in [
+            (
+                r#""image_width": 0"#,
+                "'image_width' must be an integer of at least 1",
+            ),
+            (
+                r#""samples_per_pixel": -3"#,
+                "'samples_per_pixel' must be an integer of at least 1",
+            ),
+            (
+                r#""max_depth": 2.5"#,
+                "'max_depth' must be an integer of at least 1",
+            ),
+            (
+                r#""roulette_depth": -1"#,
+                "'roulette_depth' must be an integer of at least 0",
+            ),
+        ] {
+            let text = format!(r#"{{ "camera": {{ {} }} }}"#, settings);
+            let err = Scene::parse(&text).err().unwrap();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let text = r#"{ "objects": [
+            { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "gold" }
+        ] }"#;
+        let err = Scene::parse(text).err().unwrap();
+        assert_eq!(err.to_string(), "object 0: unknown material 'gold'");
+    }
+
+    #[test]
+    fn test_lights() {
+        let text = r#"{
+            "materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+            "objects": [
+                { "type": "quad", "q": [0, 0, 0], "u": [1, 0, 0], "v": [0, 1, 0],
+                  "material": "lamp", "light": true }
+            ]
+        }"#;
+        let scene = Scene::parse(text).unwrap();
+        assert_eq!(scene.lights.objects.len(), 1);
+
+        let moved = text.replace(
+            r#""light""#,
+            r#""transform": [{ "translate": [0, 1, 0] }], "light""#,
+        );
+        let err = Scene::parse(&moved).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "object 0: a quad with a transform or motion cannot be a light"
+        );
+
+        let triangle = text
+            .replace("quad", "triangle")
+            .replace(r#""q""#, r#""a""#)
+            .replace(r#""u""#, r#""b""#)
+            .replace(r#""v""#, r#""c""#);
+        let err = Scene::parse(&triangle).err().unwrap();
+        assert_eq!(err.to_string(), "object 0: a triangle cannot be a light");
+    }
+
+    #[test]
+    fn test_textures() {
+        let text = r#"{
+            "textures": {
+                "checks": { "type": "checker", "scale": 2, "even": [1, 1, 1], "odd": [0, 0, 0] }
+            },
+            "materials": {
+                "floor": { "type": "lambertian", "albedo": "checks" },
+                "lamp": { "type": "diffuse_light", "emit": "tiles" }
+            }
+        }"#;
+        let err = Scene::parse(text).err().unwrap();
+        assert_eq!(err.to_string(), "material 'lamp': unknown texture 'tiles'");
+
+        let scene = Scene::parse(&text.replace("tiles", "checks")).unwrap();
+        assert!(scene.world.objects.is_empty());
+    }
+
+    #[test]
+    fn test_transform() {
+        let text = r#"{
+            "materials": { "white": { "type": "lambertian", "albedo": [1, 1, 1] } },
+            "objects": [
+                { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "white",
+                  "transform": [{ "scale": [2, 1, 1] }, { "translate": [0, 5, 0] }] }
+            ]
+        }"#;
+        let scene = Scene::parse(text).unwrap();
+        let bbox = scene.world.bounding_box();
+        assert_eq!(bbox.min, Vec3::build(-2.0, 4.0, -1.0));
+        assert_eq!(bbox.max, Vec3::build(2.0, 6.0, 1.0));
+
+        let err = Scene::parse(&text.replace("[2, 1, 1]", "[2, 0, 1]"))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "object 0: 'transform' cannot be inverted");
+    }
+
+    #[test]
+    fn test_integrator() {
+        let scene = Scene::parse(r#"{ "integrator": { "type": "ao", "distance": 0.5 } }"#);
+        assert_eq!(
+            scene.unwrap().camera.integrator.to_string(),
+            "ambient occlusion within 0.5"
+        );
+        let scene = Scene::parse(r#"{ "integrator": { "type": "whitted" } }"#);
+        assert_eq!(scene.unwrap().camera.integrator.to_string(), "whitted");
+
+        let err = Scene::parse(r#"{ "integrator": { "type": "photon" } }"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "integrator: unknown integrator type 'photon'"
+        );
+    }
+
+    #[test]
+    fn test_sampler() {
+        let scene = Scene::parse(r#"{ "sampler": { "type": "blue_noise" } }"#);
+        assert_eq!(scene.unwrap().camera.sampler.to_string(), "blue noise");
+        assert_eq!(
+            Scene::parse("{}").unwrap().camera.sampler.to_string(),
+            "random"
+        );
+
+        let err = Scene::parse(r#"{ "sampler": { "type": "poisson" } }"#)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "sampler: unknown sampler type 'poisson'");
+    }
+
+    #[test]
+    fn test_volumes() {
+        let text = r#"{
+            "fog": { "density": 0.1, "height": 1 },
+            "materials": { "smoke": { "type": "isotropic", "albedo": [1, 1, 1] } },
+            "objects": [
+                { "type": "heterogeneous_medium", "material": "smoke",
+                  "density": { "type": "noise", "max": 2 },
+                  "boundary": { "type": "box", "a": [-1, -1, -1], "b": [1, 1, 1] } }
+            ]
+        }"#;
+        let scene = Scene::parse(text).unwrap();
+        assert!(scene.camera.fog.is_some());
+        assert!(scene.world.bounding_box().max.x() >= 1.0);
+        assert!(scene.lights.objects.is_empty());
+
+        let err = Scene::parse(&text.replace("\"noise\"", "\"cloud\""))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "object 0: density: unknown density type 'cloud'"
+        );
+    }
+}
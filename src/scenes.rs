@@ -0,0 +1,142 @@
+//! Built-in scenes from the book, selectable without a scene file.
+
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, Lambertian, Metal};
+use crate::scene::Scene;
+use crate::sphere::Sphere;
+use crate::vec3::{random_vec3, random_vec3_in_range, Color, Point3, Vec3};
+use crate::{random_f64, random_f64_in, seed_random};
+use std::sync::Arc;
+
+/// Names of the built-in scenes, as accepted by `builtin`.
+pub const BUILTIN_NAMES: [&str; 5] = [
+    "book-cover",
+    "hollow-glass",
+    "fov-test",
+    "cornell-box",
+    "cornell-smoke",
+];
+
+/// Returns the built-in scene with the given name, or None if there is no such scene.
+/// The seed picks the random layout of scenes that have one; None picks a fresh layout.
+pub fn builtin(name: &str, seed: Option<u64>) -> Option<Scene> {
+    match name {
+        "book-cover" => Some(book_cover(seed)),
+        "hollow-glass" => Some(hollow_glass()),
+        "fov-test" => Some(fov_test()),
+        "cornell-box" => Some(cornell_box()),
+        "cornell-smoke" => Some(cornell_smoke()),
+        _ => None,
+    }
+}
+
+/// Book Cover Image - a field of small random spheres around three large ones.
+pub fn book_cover(seed: Option<u64>) -> Scene {
+    if let Some(seed) = seed {
+        seed_random(seed);
+    }
+    let mut world = HittableList::new();
+
+    let ground_material = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+    world.add(Sphere::build(
+        Point3::build(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_f64();
+            let center = Point3::build(
+                a as f64 + 0.9 * random_f64(),
+                0.2,
+                b as f64 + 0.9 * random_f64(),
+            );
+
+            if (center - Point3::build(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo: Color = random_vec3() * random_vec3();
+                    let sphere_material = Arc::new(Lambertian::from(albedo));
+                    world.add(Sphere::build(center, 0.2, sphere_material));
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo: Color = random_vec3_in_range(0.5, 1.0);
+                    let fuzz = random_f64_in(0.0, 0.5);
+                    let sphere_material = Arc::new(Metal::from(albedo, fuzz));
+                    world.add(Sphere::build(center, 0.2, sphere_material));
+                } else {
+                    // glass
+                    let sphere_material = Arc::new(Dielectric::build(1.5));
+                    world.add(Sphere::build(center, 0.2, sphere_material));
+                }
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::build(1.5));
+    world.add(Sphere::build(Point3::build(0.0, 1.0, 0.0), 1.0, material1));
+
+    let material2 = Arc::new(Lambertian::build(0.4, 0.2, 0.1));
+    world.add(Sphere::build(Point3::build(-4.0, 1.0, 0.0), 1.0, material2));
+
+    let material3 = Arc::new(Metal::build(0.7, 0.6, 0.5, 0.0));
+    world.add(Sphere::build(Point3::build(4.0, 1.0, 0.0), 1.0, material3));
+
+    let mut scene = Scene::build(world);
+    let cam = &mut scene.camera;
+
+    cam.aspect_ratio = 16.0 / 9.0;
+    cam.image_width = 1200;
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 50;
+
+    cam.vfov = 20.0;
+    cam.look_from = Point3::build(13.0, 2.0, 3.0);
+    cam.look_at = Point3::build(0.0, 0.0, 0.0);
+    cam.vup = Vec3::build(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.6;
+    cam.focus_dist = 10.0;
+
+    scene
+}
+
+/// Image 18 - With Hollow Glass Sphere
+pub fn hollow_glass() -> Scene {
+    Scene::parse(include_str!("../scenes/hollow_glass.json")).expect("invalid built-in scene")
+}
+
+/// Image 19 - Testing Camera FOV
+pub fn fov_test() -> Scene {
+    Scene::parse(include_str!("../scenes/fov_test.json")).expect("invalid built-in scene")
+}
+
+/// Cornell Box - a closed room of colored walls lit through a hole in the ceiling.
+pub fn cornell_box() -> Scene {
+    Scene::parse(include_str!("../scenes/cornell_box.json")).expect("invalid built-in scene")
+}
+
+/// Cornell Box with Smoke - the two blocks replaced by volumes of smoke and fog.
+pub fn cornell_smoke() -> Scene {
+    Scene::parse(include_str!("../scenes/cornell_smoke.json")).expect("invalid built-in scene")
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builtin_scenes_load() {
+        for name in BUILTIN_NAMES {
+            let scene = builtin(name, Some(1)).unwrap();
+            assert!(!scene.world.objects.is_empty(), "{}", name);
+        }
+        assert!(builtin("teapot", None).is_none());
+
+        let cornell = builtin("cornell-box", None).unwrap();
+        assert_eq!(cornell.lights.objects.len(), 1);
+    }
+}
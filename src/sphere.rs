@@ -1,21 +1,28 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::onb::Onb;
+use crate::random_f64;
 use crate::ray::Ray;
-use crate::vec3::{dot, Point3};
+use crate::vec3::{dot, random_unit_vector, Point3, Vec3};
+use std::f64::consts::PI;
 use std::fmt::Display;
 use std::ops::RangeInclusive;
-use std::rc::Rc;
+use std::sync::Arc;
 
-/// Describes a Hittable sphere.
+/// Describes a Hittable sphere. A moving sphere travels from center at time 0 to
+/// center + center_vec at time 1.
 pub struct Sphere {
     pub center: Point3,
+    pub center_vec: Vec3,
     pub radius: f64,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
-        let oc = r.orig - self.center;
+        let center = self.center_at(r.time());
+        let oc = r.orig - center;
         let a = r.direction().length_squared();
         let half_b = dot(&oc, &r.direction());
         let c = oc.length_squared() - self.radius * self.radius;
@@ -37,12 +44,65 @@ impl Hittable for Sphere {
 
         rec.t = root;
         rec.p = r.at(rec.t);
-        let outward_normal = (rec.p - self.center) / self.radius;
+        let outward_normal = (rec.p - center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
-        rec.mat = Rc::clone(&self.mat);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
+        rec.mat = Arc::clone(&self.mat);
+        rec.object_id = 0;
 
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        // The radius is negative for the inner surface of hollow spheres.
+        // A moving sphere is boxed over its whole path between times 0 and 1.
+        let rvec = Vec3::build(self.radius, self.radius, self.radius);
+        let end = self.center_at(1.0);
+        Aabb::build(self.center - rvec, self.center + rvec)
+            .surrounding(&Aabb::build(end - rvec, end + rvec))
+    }
+
+    /// Returns the density of `random` choosing the direction: uniform over the cone
+    /// of directions from origin that meet the sphere. Lights are sampled where they
+    /// are at time 0.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(
+            &Ray::build(*origin, *direction),
+            0.001..=f64::INFINITY,
+            &mut rec,
+        ) {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 0.0;
+        }
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return random_unit_vector();
+        }
+
+        // Pick a direction uniformly within the cone around the Z axis, then turn the
+        // cone to face the sphere.
+        let r1 = random_f64();
+        let r2 = random_f64();
+        let z = 1.0 + r2 * ((1.0 - radius_squared / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+        let local = Vec3::build(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+        Onb::build(&direction).local(&local)
+    }
 }
 
 /// For debugging. Returns the r, g, b, values for the albedo of the sphere's material.
@@ -52,14 +112,70 @@ impl Display for Sphere {
     }
 }
 
-unsafe impl Sync for Sphere {}
-
 impl Sphere {
-    pub fn build(center: Point3, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn build(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self::moving(center, center, radius, material)
+    }
+
+    /// Returns the center of the sphere at time tm.
+    fn center_at(&self, tm: f64) -> Point3 {
+        self.center + tm * self.center_vec
+    }
+
+    /// Returns the u, v coordinates of a point p on the unit sphere centered at the
+    /// origin. u runs from 0 to 1 around the Y axis starting from X=-1, and v runs
+    /// from 0 at Y=-1 to 1 at Y=+1.
+    fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Builds a new sphere moving from center1 at time 0 to center2 at time 1.
+    pub fn moving(
+        center1: Point3,
+        center2: Point3,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
         Self {
-            center,
+            center: center1,
+            center_vec: center2 - center1,
             radius,
             mat: material,
         }
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn test_moving() {
+        let sphere = Sphere::moving(
+            Point3::new(),
+            Point3::build(2.0, 0.0, 0.0),
+            0.5,
+            Arc::new(Lambertian::build(0.5, 0.5, 0.5)),
+        );
+        assert_eq!(
+            sphere.bounding_box(),
+            Aabb::build(
+                Point3::build(-0.5, -0.5, -0.5),
+                Point3::build(2.5, 0.5, 0.5)
+            )
+        );
+
+        // A ray through x = 2 only finds the sphere once it has arrived there.
+        let mut rec = HitRecord::new();
+        let origin = Point3::build(2.0, 0.0, 5.0);
+        let down = Vec3::build(0.0, 0.0, -1.0);
+        assert!(!sphere.hit(&Ray::build_timed(origin, down, 0.0), 0.0..=10.0, &mut rec));
+        assert!(sphere.hit(&Ray::build_timed(origin, down, 1.0), 0.0..=10.0, &mut rec));
+        assert_eq!(rec.t, 4.5);
+    }
+}
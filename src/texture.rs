@@ -0,0 +1,409 @@
+use crate::image::Image;
+use crate::noise::{Perlin, Worley};
+use crate::vec3::{Color, Point3};
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// Describes a color that varies over a surface or through space.
+pub trait Texture: Display + Send + Sync {
+    /// Returns the texture color at surface coordinates u, v and hit point p.
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    /// Returns the color of the texture if it is the same everywhere, or None if it
+    /// varies.
+    fn solid_color(&self) -> Option<Color> {
+        None
+    }
+}
+
+/// Describes a texture of a single color everywhere.
+pub struct SolidColor {
+    albedo: Color,
+}
+
+/// Describes a 3D checker pattern that fills space with alternating cubes of two
+/// textures, regardless of how the surface is parameterized.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+/// Describes a 2D checker pattern laid out over the surface's u, v coordinates.
+pub struct UvCheckerTexture {
+    columns: f64, // Number of checks across u
+    rows: f64,    // Number of checks across v
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+/// Describes a texture that maps an Image of linear colors over u, v coordinates.
+pub struct ImageTexture {
+    image: Image,
+}
+
+/// Describes a texture of smooth Perlin noise shading a single color.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64, // Frequency of the noise
+    color: Color,
+}
+
+/// Describes a texture of Perlin turbulence, several octaves of noise summed, shading
+/// a single color.
+pub struct TurbulenceTexture {
+    noise: Perlin,
+    scale: f64, // Frequency of the lowest octave
+    depth: i32, // Number of octaves
+    color: Color,
+}
+
+/// Describes a marble texture of veins running along z, bent by turbulence.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,      // Frequency of the veins
+    turbulence: f64, // How strongly the veins are bent
+    base: Color,
+    vein: Color,
+}
+
+/// Describes a wood texture of growth rings around the y axis, warped by turbulence.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,      // Rings per unit distance from the axis
+    turbulence: f64, // How strongly the rings are warped
+    light: Color,
+    dark: Color,
+}
+
+/// Describes a cellular texture that blends from one color at Worley feature points
+/// to another at the cell borders.
+pub struct WorleyTexture {
+    noise: Worley,
+    scale: f64, // Cells per unit distance
+    near: Color,
+    far: Color,
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        Some(self.albedo)
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i64;
+        let y = (self.inv_scale * p.y()).floor() as i64;
+        let z = (self.inv_scale * p.z()).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+impl Texture for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let i = (u * self.columns).floor() as i64;
+        let j = (v * self.rows).floor() as i64;
+
+        if (i + j) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // With no image data, return solid cyan as a debugging aid.
+        if self.image.width == 0 || self.image.height == 0 {
+            return Color::build(0.0, 1.0, 1.0);
+        }
+
+        // Clamp input texture coordinates to [0,1] x [1,0], flipping v to image rows.
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+
+        let i = ((u * self.image.width as f64) as usize).min(self.image.width - 1);
+        let j = ((v * self.image.height as f64) as usize).min(self.image.height - 1);
+        self.image.pixel(i, j)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        0.5 * (1.0 + self.noise.noise(&(self.scale * *p))) * self.color
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        self.noise.turb(&(self.scale * *p), self.depth).min(1.0) * self.color
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let phase = self.scale * p.z() + self.turbulence * self.noise.turb(p, 7);
+        let t = 0.5 * (1.0 + phase.sin());
+        (1.0 - t) * self.vein + t * self.base
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let rings = self.scale * radius + self.turbulence * self.noise.turb(p, 4);
+        let t = rings - rings.floor();
+        (1.0 - t) * self.light + t * self.dark
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let t = self.noise.noise(&(self.scale * *p));
+        (1.0 - t) * self.near + t * self.far
+    }
+}
+
+impl Display for SolidColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.albedo.e[0], self.albedo.e[1], self.albedo.e[2]
+        )
+    }
+}
+
+impl Display for CheckerTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "checker({} / {})", self.even, self.odd)
+    }
+}
+
+impl Display for UvCheckerTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "uv checker({} / {})", self.even, self.odd)
+    }
+}
+
+impl Display for ImageTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} image", self.image.width, self.image.height)
+    }
+}
+
+impl Display for NoiseTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "noise({})", self.scale)
+    }
+}
+
+impl Display for TurbulenceTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "turbulence({}, {} octaves)", self.scale, self.depth)
+    }
+}
+
+impl Display for MarbleTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "marble({})", self.scale)
+    }
+}
+
+impl Display for WoodTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "wood({})", self.scale)
+    }
+}
+
+impl Display for WorleyTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worley({})", self.scale)
+    }
+}
+
+impl SolidColor {
+    /// Builds a new SolidColor from RGB values.
+    pub fn build(r: f64, g: f64, b: f64) -> Self {
+        Self {
+            albedo: Color::build(r, g, b),
+        }
+    }
+
+    /// Constructs a new SolidColor from an existing Color.
+    pub fn from(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl CheckerTexture {
+    /// Builds a new CheckerTexture of cubes with sides of length scale.
+    pub fn build(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    /// Constructs a new CheckerTexture alternating between two colors.
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::build(
+            scale,
+            Arc::new(SolidColor::from(even)),
+            Arc::new(SolidColor::from(odd)),
+        )
+    }
+}
+
+impl UvCheckerTexture {
+    /// Builds a new UvCheckerTexture with the given number of checks across u and v.
+    pub fn build(columns: f64, rows: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            columns,
+            rows,
+            even,
+            odd,
+        }
+    }
+
+    /// Constructs a new UvCheckerTexture alternating between two colors.
+    pub fn from_colors(columns: f64, rows: f64, even: Color, odd: Color) -> Self {
+        Self::build(
+            columns,
+            rows,
+            Arc::new(SolidColor::from(even)),
+            Arc::new(SolidColor::from(odd)),
+        )
+    }
+}
+
+impl ImageTexture {
+    /// Builds a new ImageTexture from an Image of linear colors.
+    pub fn build(image: Image) -> Self {
+        Self { image }
+    }
+}
+
+impl NoiseTexture {
+    /// Builds a new NoiseTexture of the given frequency from a seeded Perlin generator.
+    pub fn build(seed: u64, scale: f64, color: Color) -> Self {
+        Self {
+            noise: Perlin::build(seed),
+            scale,
+            color,
+        }
+    }
+}
+
+impl TurbulenceTexture {
+    /// Builds a new TurbulenceTexture summing depth octaves of seeded Perlin noise.
+    pub fn build(seed: u64, scale: f64, depth: i32, color: Color) -> Self {
+        Self {
+            noise: Perlin::build(seed),
+            scale,
+            depth,
+            color,
+        }
+    }
+}
+
+impl MarbleTexture {
+    /// Builds a new MarbleTexture with veins of one color through a base of another.
+    pub fn build(seed: u64, scale: f64, turbulence: f64, base: Color, vein: Color) -> Self {
+        Self {
+            noise: Perlin::build(seed),
+            scale,
+            turbulence,
+            base,
+            vein,
+        }
+    }
+}
+
+impl WoodTexture {
+    /// Builds a new WoodTexture with rings shading from a light to a dark color.
+    pub fn build(seed: u64, scale: f64, turbulence: f64, light: Color, dark: Color) -> Self {
+        Self {
+            noise: Perlin::build(seed),
+            scale,
+            turbulence,
+            light,
+            dark,
+        }
+    }
+}
+
+impl WorleyTexture {
+    /// Builds a new WorleyTexture of cells shading from near to far colors.
+    pub fn build(seed: u64, scale: f64, near: Color, far: Color) -> Self {
+        Self {
+            noise: Worley::build(seed),
+            scale,
+            near,
+            far,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checkers() {
+        let (white, black) = (Color::build(1.0, 1.0, 1.0), Color::new());
+        let origin = Point3::new();
+
+        let checker = CheckerTexture::from_colors(1.0, white, black);
+        assert_eq!(
+            checker.value(0.0, 0.0, &Point3::build(0.5, 0.5, 0.5)),
+            white
+        );
+        assert_eq!(
+            checker.value(0.0, 0.0, &Point3::build(-0.5, 0.5, 0.5)),
+            black
+        );
+
+        let uv_checker = UvCheckerTexture::from_colors(2.0, 1.0, white, black);
+        assert_eq!(uv_checker.value(0.25, 0.5, &origin), white);
+        assert_eq!(uv_checker.value(0.75, 0.5, &origin), black);
+
+        assert_eq!(SolidColor::from(white).solid_color(), Some(white));
+        assert_eq!(checker.solid_color(), None);
+    }
+
+    #[test]
+    fn test_procedural() {
+        let (white, black) = (Color::build(1.0, 1.0, 1.0), Color::new());
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(NoiseTexture::build(1, 4.0, white)),
+            Box::new(TurbulenceTexture::build(1, 4.0, 7, white)),
+            Box::new(MarbleTexture::build(1, 4.0, 10.0, white, black)),
+            Box::new(WoodTexture::build(1, 4.0, 0.5, white, black)),
+            Box::new(WorleyTexture::build(1, 4.0, white, black)),
+        ];
+
+        for tex in &textures {
+            for n in 0..50 {
+                let p = Point3::build(n as f64 * 0.31, n as f64 * 0.17, n as f64 * -0.23);
+                let c = tex.value(0.0, 0.0, &p);
+                assert!((0.0..=1.0).contains(&c.x()), "{} at {}", tex, p);
+                assert_eq!(c.x(), c.y());
+            }
+        }
+    }
+}
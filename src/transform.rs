@@ -0,0 +1,355 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::{unit_vector, Point3, Vec3};
+use std::fmt::Display;
+use std::ops::{Mul, RangeInclusive};
+use std::sync::Arc;
+
+/// Describes an affine transform of 3D space as a 4x4 matrix acting on column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub m: [[f64; 4]; 4],
+}
+
+/// Describes a Hittable placed in the world by a Transform. Many Instances can share
+/// one object, so a mesh can be placed many times while stored once. A moving Instance
+/// is also translated by motion over the time from 0 to 1.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform, // Object space to world space
+    inverse: Transform,   // World space to object space
+    motion: Vec3,         // World space offset at time 1
+    bbox: Aabb,
+}
+
+impl Default for Transform {
+    /// The default Transform is the identity, which leaves everything in place.
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, row) in self.m.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "; " };
+            write!(f, "{}{} {} {} {}", sep, row[0], row[1], row[2], row[3])?;
+        }
+        Ok(())
+    }
+}
+
+/// Composes two Transforms: `a * b` applies b first, then a.
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Self { m }
+    }
+}
+
+impl Transform {
+    /// Returns the inverse Transform, or None if the Transform squashes space flat.
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting, on the matrix and the
+        // identity side by side.
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Self { m: inv })
+    }
+
+    /// Constructs the identity Transform.
+    pub const fn identity() -> Self {
+        Self {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Constructs the identity Transform.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Constructs a rotation by angle degrees about an axis through the origin,
+    /// counterclockwise looking down the axis toward the origin.
+    pub fn rotate(axis: &Vec3, angle: f64) -> Self {
+        let a = unit_vector(axis);
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (s, c) = angle.to_radians().sin_cos();
+        let t = 1.0 - c;
+        Self {
+            m: [
+                [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+                [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+                [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Constructs a scaling by a separate factor along each axis.
+    pub fn scale(factors: &Vec3) -> Self {
+        let mut m = Self::identity().m;
+        for (axis, row) in m.iter_mut().take(3).enumerate() {
+            row[axis] = factors[axis];
+        }
+        Self { m }
+    }
+
+    /// Returns this Transform followed by next.
+    pub fn then(&self, next: &Transform) -> Self {
+        *next * *self
+    }
+
+    /// Constructs a translation by offset.
+    pub fn translate(offset: &Vec3) -> Self {
+        let mut m = Self::identity().m;
+        for (axis, row) in m.iter_mut().take(3).enumerate() {
+            row[3] = offset[axis];
+        }
+        Self { m }
+    }
+
+    /// Returns the normal n transformed to stay perpendicular to the surface it came
+    /// from. Call this on the inverse of the Transform applied to the surface.
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        // Multiply by the transpose.
+        let m = &self.m;
+        Vec3::build(
+            m[0][0] * n.x() + m[1][0] * n.y() + m[2][0] * n.z(),
+            m[0][1] * n.x() + m[1][1] * n.y() + m[2][1] * n.z(),
+            m[0][2] * n.x() + m[1][2] * n.y() + m[2][2] * n.z(),
+        )
+    }
+
+    /// Returns the point p transformed, translation included.
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::build(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// Returns the direction v transformed, ignoring translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::build(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        if !self.object.hit(&self.object_ray(r), ray_t, rec) {
+            return false;
+        }
+        self.hit_to_world(r, rec);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn shadow_hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        if !self.object.shadow_hit(&self.object_ray(r), ray_t, rec) {
+            return false;
+        }
+        self.hit_to_world(r, rec);
+        true
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> f64 {
+        self.object.transmittance(&self.object_ray(r), ray_t)
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "instance [{}] of {}", self.transform, self.object)
+    }
+}
+
+impl Instance {
+    /// Builds a new Instance placing object in the world by transform. Returns None if
+    /// the transform cannot be inverted.
+    pub fn build(object: Arc<dyn Hittable>, transform: Transform) -> Option<Self> {
+        Self::moving(object, transform, Vec3::new())
+    }
+
+    /// Carries a hit on the object, found along the object-space version of r, back
+    /// out to world space. Transforming the normal with the inverse transpose keeps
+    /// which side of the surface the ray came from.
+    fn hit_to_world(&self, r: &Ray, rec: &mut HitRecord) {
+        let offset = r.time() * self.motion;
+        rec.p = self.transform.transform_point(&rec.p) + offset;
+        rec.normal = unit_vector(&self.inverse.transform_normal(&rec.normal));
+    }
+
+    /// Builds a new Instance placed by transform at time 0, and moved on by motion at
+    /// time 1. Returns None if the transform cannot be inverted.
+    pub fn moving(object: Arc<dyn Hittable>, transform: Transform, motion: Vec3) -> Option<Self> {
+        let inverse = transform.inverse()?;
+
+        // Box the transformed corners of the object's box.
+        let object_box = object.bounding_box();
+        let mut bbox = Aabb::empty();
+        if !object_box.is_empty() {
+            for corner in 0..8 {
+                let pick = |axis: usize| match corner >> axis & 1 {
+                    0 => object_box.min[axis],
+                    _ => object_box.max[axis],
+                };
+                let p = transform.transform_point(&Point3::build(pick(0), pick(1), pick(2)));
+                bbox = bbox.surrounding(&Aabb::build(p, p));
+            }
+        }
+
+        // Box the whole path, from time 0 to time 1.
+        let bbox = bbox.surrounding(&Aabb::build(bbox.min + motion, bbox.max + motion));
+
+        Some(Self {
+            object,
+            transform,
+            inverse,
+            motion,
+            bbox,
+        })
+    }
+
+    /// Returns r in object space, where the object is intersected. The direction is
+    /// not renormalized, so hit parameters t mean the same point along the ray in
+    /// both spaces.
+    fn object_ray(&self, r: &Ray) -> Ray {
+        let offset = r.time() * self.motion;
+        Ray::build_timed(
+            self.inverse.transform_point(&(r.orig - offset)),
+            self.inverse.transform_vector(&r.direction()),
+            r.time(),
+        )
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_transform() {
+        let t = Transform::scale(&Vec3::build(2.0, 1.0, 1.0))
+            .then(&Transform::rotate(&Vec3::build(0.0, 0.0, 1.0), 90.0))
+            .then(&Transform::translate(&Vec3::build(0.0, 0.0, 5.0)));
+        let p = Point3::build(1.0, 0.0, 0.0);
+        assert_near(t.transform_point(&p), Point3::build(0.0, 2.0, 5.0));
+        assert_near(t.transform_vector(&p), Vec3::build(0.0, 2.0, 0.0));
+
+        let inverse = t.inverse().unwrap();
+        assert_near(inverse.transform_point(&t.transform_point(&p)), p);
+        assert!(Transform::scale(&Vec3::build(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn test_instance() {
+        // A unit sphere stretched to an ellipsoid 4 long in x, then moved to x = 10.
+        let sphere = Sphere::build(
+            Point3::new(),
+            1.0,
+            Arc::new(Lambertian::build(0.5, 0.5, 0.5)),
+        );
+        let transform = Transform::scale(&Vec3::build(4.0, 1.0, 1.0))
+            .then(&Transform::translate(&Vec3::build(10.0, 0.0, 0.0)));
+        let instance = Instance::build(Arc::new(sphere), transform).unwrap();
+        assert_eq!(
+            instance.bounding_box(),
+            Aabb::build(
+                Point3::build(6.0, -1.0, -1.0),
+                Point3::build(14.0, 1.0, 1.0)
+            )
+        );
+
+        let mut rec = HitRecord::new();
+        let r = Ray::build(Point3::new(), Vec3::build(1.0, 0.0, 0.0));
+        assert!(instance.hit(&r, 0.0..=f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 6.0);
+        assert_near(rec.p, Point3::build(6.0, 0.0, 0.0));
+        assert_near(rec.normal, Vec3::build(-1.0, 0.0, 0.0));
+        assert!(rec.front_face);
+
+        // Off the tip of the ellipsoid, the normal leans toward x much less than the
+        // stretched sphere normal would.
+        let r = Ray::build(Point3::build(12.0, 5.0, 0.0), Vec3::build(0.0, -1.0, 0.0));
+        assert!(instance.hit(&r, 0.0..=f64::INFINITY, &mut rec));
+        let expected = unit_vector(&Vec3::build(0.5 / 4.0, 0.75_f64.sqrt(), 0.0));
+        assert_near(rec.normal, expected);
+    }
+
+    #[test]
+    fn test_moving_instance() {
+        let sphere = Sphere::build(
+            Point3::new(),
+            1.0,
+            Arc::new(Lambertian::build(0.5, 0.5, 0.5)),
+        );
+        let motion = Vec3::build(0.0, 4.0, 0.0);
+        let instance = Instance::moving(Arc::new(sphere), Transform::new(), motion).unwrap();
+        assert_eq!(instance.bounding_box().max, Point3::build(1.0, 5.0, 1.0));
+
+        // Halfway through, the sphere has risen to y = 2.
+        let mut rec = HitRecord::new();
+        let r = Ray::build_timed(
+            Point3::build(0.0, 2.0, 5.0),
+            Vec3::build(0.0, 0.0, -1.0),
+            0.5,
+        );
+        assert!(instance.hit(&r, 0.0..=f64::INFINITY, &mut rec));
+        assert_near(rec.p, Point3::build(0.0, 2.0, 1.0));
+        assert!(!instance.hit(&Ray::build(r.orig, r.dir), 0.0..=f64::INFINITY, &mut rec));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{cross, dot, unit_vector, Point3, Vec3};
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Describes a Hittable triangle, optionally with a normal and a texture coordinate at
+/// each vertex. The front face is the side that (b - a) × (c - a) points toward.
+pub struct Triangle {
+    p: [Point3; 3],
+    n: Option<[Vec3; 3]>,        // Vertex normals, for smooth shading
+    uv: Option<[(f64, f64); 3]>, // Vertex texture coordinates
+    mat: Arc<dyn Material>,
+}
+
+/// Describes a face of a TriangleMesh by indices into the mesh's vertex buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/// Describes a mesh of triangles sharing vertex buffers and a single material.
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub mat: Arc<dyn Material>,
+}
+
+/// Describes one face of a shared TriangleMesh as a Hittable of its own.
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        let Some(hit) = intersect(&self.p, r, ray_t) else {
+            return false;
+        };
+        fill_record(&self.p, self.n.as_ref(), self.uv.as_ref(), r, hit, rec);
+        rec.mat = Arc::clone(&self.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_box(&self.p)
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        let p = self.positions();
+        let Some(hit) = intersect(&p, r, ray_t) else {
+            return false;
+        };
+
+        let mesh = &self.mesh;
+        let face = &mesh.faces[self.face];
+        let n = face.normals.map(|i| i.map(|i| mesh.normals[i]));
+        let uv = face.uvs.map(|i| i.map(|i| mesh.uvs[i]));
+        fill_record(&p, n.as_ref(), uv.as_ref(), r, hit, rec);
+        rec.mat = Arc::clone(&mesh.mat);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_box(&self.positions())
+    }
+}
+
+impl Display for Triangle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "triangle [{}] [{}] [{}] {}",
+            self.p[0], self.p[1], self.p[2], self.mat
+        )
+    }
+}
+
+impl Display for TriangleMesh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mesh of {} triangles, {} vertices {}",
+            self.faces.len(),
+            self.positions.len(),
+            self.mat
+        )
+    }
+}
+
+impl Display for MeshTriangle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "face {} of {}", self.face, self.mesh)
+    }
+}
+
+impl Triangle {
+    /// Builds a new flat-shaded Triangle from its three corners.
+    pub fn build(a: Point3, b: Point3, c: Point3, material: Arc<dyn Material>) -> Self {
+        Self::from([a, b, c], None, None, material)
+    }
+
+    /// Constructs a new Triangle from its corners and, optionally, the normal and
+    /// texture coordinate at each corner.
+    pub fn from(
+        p: [Point3; 3],
+        n: Option<[Vec3; 3]>,
+        uv: Option<[(f64, f64); 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            p,
+            n,
+            uv,
+            mat: material,
+        }
+    }
+}
+
+impl TriangleMesh {
+    /// Builds a new TriangleMesh with no normals or texture coordinates.
+    pub fn build(
+        positions: Vec<Point3>,
+        faces: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: faces
+                .into_iter()
+                .map(|positions| MeshFace {
+                    positions,
+                    ..Default::default()
+                })
+                .collect(),
+            mat: material,
+        }
+    }
+
+    /// Returns a HittableList holding each face of the mesh as a MeshTriangle. The
+    /// faces all share the one mesh, and through it the one material.
+    pub fn triangles(self) -> HittableList {
+        let mesh = Arc::new(self);
+        let mut list = HittableList::new();
+        for face in 0..mesh.faces.len() {
+            list.add(MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                face,
+            });
+        }
+        list
+    }
+}
+
+impl MeshTriangle {
+    /// Returns the corners of the face.
+    fn positions(&self) -> [Point3; 3] {
+        self.mesh.faces[self.face]
+            .positions
+            .map(|i| self.mesh.positions[i])
+    }
+}
+
+/// Returns the Aabb of a triangle, padded so that axis-aligned triangles have volume.
+fn triangle_box(p: &[Point3; 3]) -> Aabb {
+    Aabb::build(p[0], p[1])
+        .surrounding(&Aabb::build(p[2], p[2]))
+        .padded(0.0001)
+}
+
+/// Intersects a Ray with the triangle p using the Möller-Trumbore algorithm. Returns
+/// the hit parameter t and the barycentric weights of the second and third corners.
+fn intersect(p: &[Point3; 3], r: &Ray, ray_t: RangeInclusive<f64>) -> Option<(f64, f64, f64)> {
+    let edge1 = p[1] - p[0];
+    let edge2 = p[2] - p[0];
+    let pvec = cross(&r.direction(), &edge2);
+    let det = dot(&edge1, &pvec);
+
+    // No hit if the ray is parallel to the triangle.
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.orig - p[0];
+    let b1 = dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(&tvec, &edge1);
+    let b2 = dot(&r.direction(), &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(&edge2, &qvec) * inv_det;
+    ray_t.contains(&t).then_some((t, b1, b2))
+}
+
+/// Fills in a HitRecord for a hit found by `intersect`. The face is decided by the
+/// geometric normal; the vertex normals, when present, only bend the shading normal
+/// within that side.
+fn fill_record(
+    p: &[Point3; 3],
+    n: Option<&[Vec3; 3]>,
+    uv: Option<&[(f64, f64); 3]>,
+    r: &Ray,
+    (t, b1, b2): (f64, f64, f64),
+    rec: &mut HitRecord,
+) {
+    let b0 = 1.0 - b1 - b2;
+
+    rec.t = t;
+    rec.p = r.at(t);
+    rec.object_id = 0;
+    let outward_normal = unit_vector(&cross(&(p[1] - p[0]), &(p[2] - p[0])));
+    rec.set_face_normal(r, &outward_normal);
+
+    if let Some(n) = n {
+        let shading_normal = unit_vector(&(b0 * n[0] + b1 * n[1] + b2 * n[2]));
+        rec.normal = match dot(&shading_normal, &rec.normal) < 0.0 {
+            true => -shading_normal,
+            false => shading_normal,
+        };
+    }
+
+    (rec.u, rec.v) = match uv {
+        Some(uv) => (
+            b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+            b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+        ),
+        None => (b1, b2),
+    };
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn test_hit() {
+        let mat = Arc::new(Lambertian::build(0.5, 0.5, 0.5));
+        let corners = [
+            Point3::build(0.0, 0.0, 0.0),
+            Point3::build(1.0, 0.0, 0.0),
+            Point3::build(0.0, 1.0, 0.0),
+        ];
+        let tilted = Vec3::build(0.0, 0.6, 0.8);
+        let triangle = Triangle::from(
+            corners,
+            Some([tilted; 3]),
+            Some([(0.0, 0.0), (1.0, 0.0), (0.0, 0.5)]),
+            mat.clone(),
+        );
+        let mut rec = HitRecord::new();
+
+        let toward = Ray::build(Point3::build(0.25, 0.5, 2.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(triangle.hit(&toward, 0.0..=f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 2.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, tilted);
+        assert_eq!((rec.u, rec.v), (0.25, 0.25));
+
+        // From behind, the shading normal flips with the face.
+        let behind = Ray::build(Point3::build(0.25, 0.5, -2.0), Vec3::build(0.0, 0.0, 1.0));
+        assert!(triangle.hit(&behind, 0.0..=f64::INFINITY, &mut rec));
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, -tilted);
+
+        let past = Ray::build(Point3::build(0.75, 0.5, 2.0), Vec3::build(0.0, 0.0, -1.0));
+        assert!(!triangle.hit(&past, 0.0..=f64::INFINITY, &mut rec));
+
+        // The same triangle as a mesh face hits in the same place.
+        let mesh = TriangleMesh::build(corners.to_vec(), vec![[0, 1, 2]], mat).triangles();
+        assert!(mesh.hit(&toward, 0.0..=f64::INFINITY, &mut rec));
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.normal, Vec3::build(0.0, 0.0, 1.0));
+        assert_eq!(mesh.bounding_box(), triangle.bounding_box());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{random_f64, random_f64_in};
 use std::{
+    f64::consts::PI,
     fmt::Display,
     ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub},
 };
@@ -230,6 +231,18 @@ impl Vec3 {
     }
 }
 
+/// Returns the unit Vec3 in the hemisphere around +Z that the point r1,r2 of the unit
+/// square maps to. Evenly spread points give directions with density proportional to
+/// their z component, cos(theta) / pi.
+pub fn cosine_direction(r1: f64, r2: f64) -> Vec3 {
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+
+    Vec3::build(x, y, z)
+}
+
 /// Returns the cross product of two Vec3s.
 pub fn cross(lhs: &Vec3, rhs: &Vec3) -> Vec3 {
     Vec3 {
@@ -246,12 +259,24 @@ pub fn dot(lhs: &Vec3, rhs: &Vec3) -> f64 {
     lhs.e[0] * rhs.e[0] + lhs.e[1] * rhs.e[1] + lhs.e[2] * rhs.e[2]
 }
 
+/// Converts from gamma space back to linear space by squaring the gamma
+/// component, undoing `linear_to_gamma`.
+pub fn gamma_to_linear(gamma_component: f64) -> f64 {
+    gamma_component * gamma_component
+}
+
 /// Converts from linear space to gamma space by taking the square root
 /// of the linear component.
 pub fn linear_to_gamma(linear_component: f64) -> f64 {
     linear_component.sqrt()
 }
 
+/// Returns a random unit Vec3 in the hemisphere around +Z, with density proportional
+/// to its z component, cos(theta) / pi.
+pub fn random_cosine_direction() -> Vec3 {
+    cosine_direction(random_f64(), random_f64())
+}
+
 /// Returns a random Vec3 with x, y, and z in the range 0..=1.
 pub fn random_vec3() -> Vec3 {
     Vec3 {
@@ -323,6 +348,24 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
     r_out_perp + r_out_parallel
 }
 
+/// Returns the point of the unit disk in the XY plane that the point r1,r2 of the
+/// unit square maps to, by Shirley and Chiu's concentric mapping, which keeps evenly
+/// spread points evenly spread.
+pub fn unit_disk_point(r1: f64, r2: f64) -> Vec3 {
+    let a = 2.0 * r1 - 1.0;
+    let b = 2.0 * r2 - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Vec3::new();
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    Vec3::build(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
 /// Return the unit vector of a Vec3.
 pub fn unit_vector(v: &Vec3) -> Vec3 {
     *v / v.length()
@@ -368,4 +411,14 @@ mod test {
         let z = Vec3::build(0.0, 0.0, 1.0);
         assert_eq!(cross(&x, &y), z);
     }
+
+    #[test]
+    fn test_unit_disk_point() {
+        assert_eq!(unit_disk_point(0.5, 0.5), Vec3::new());
+        assert!((unit_disk_point(1.0, 0.5) - Vec3::build(1.0, 0.0, 0.0)).near_zero());
+        assert!((unit_disk_point(0.5, 0.0) - Vec3::build(0.0, -1.0, 0.0)).near_zero());
+        for (r1, r2) in [(0.0, 0.0), (0.1, 0.9), (0.7, 0.3), (1.0, 1.0)] {
+            assert!(unit_disk_point(r1, r2).length() <= 1.0 + 1e-12);
+        }
+    }
 }
@@ -0,0 +1,482 @@
+//! Participating media whose density varies through space, and a scene-wide
+//! exponential height fog.
+//!
+//! A HeterogeneousMedium samples where rays scatter with delta tracking: it takes
+//! exponential steps as if the whole volume were as dense as its densest point, and
+//! accepts each step as a real collision with probability density / max density.
+//! Shadow rays pass through it instead, dimmed by its transmittance, which is
+//! estimated the same way with ratio tracking: it multiplies in the chance of passing
+//! each tentative collision.
+
+use crate::aabb::Aabb;
+use crate::constant_medium::span_inside;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::noise::Perlin;
+use crate::random_f64;
+use crate::ray::Ray;
+use crate::vec3::{Color, Point3, Vec3};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Describes how the density of a heterogeneous medium varies through space.
+pub trait DensityField: Display + Send + Sync {
+    /// Returns the density of the medium at p.
+    fn density(&self, p: &Point3) -> f64;
+
+    /// Returns an upper bound on the density anywhere in the field.
+    fn max_density(&self) -> f64;
+}
+
+/// Describes a density field of Perlin turbulence, for smoke and clouds.
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,   // Frequency of the lowest octave
+    depth: i32,   // Number of octaves
+    density: f64, // Density where the turbulence is strongest
+}
+
+/// Describes a density field sampled on a regular 3D grid spanning a box, with
+/// trilinear interpolation between the samples.
+pub struct GridDensity {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    values: Vec<f64>, // Samples with x varying fastest, then y, then z
+    bbox: Aabb,       // Region of space the grid spans; density is zero outside it
+    max: f64,
+}
+
+/// Describes a volume whose density varies through space, filling a convex boundary.
+pub struct HeterogeneousMedium {
+    boundary: Arc<dyn Hittable>,
+    field: Arc<dyn DensityField>,
+    phase_function: Arc<dyn Material>,
+}
+
+/// Describes a fog filling the whole scene, thinning exponentially with height:
+/// density(y) = density * e^(-falloff * (y - height)).
+#[derive(Clone, Copy, Debug)]
+pub struct HeightFog {
+    pub density: f64, // Density at the base height
+    pub falloff: f64, // How quickly the fog thins going up
+    pub height: f64,  // Base height of the fog
+    pub color: Color, // Light the fog scatters toward the camera
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        self.density * self.noise.turb(&(self.scale * *p), self.depth).min(1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.density
+    }
+}
+
+impl DensityField for GridDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        if self.values.is_empty() {
+            return 0.0;
+        }
+
+        // Find the position in grid units, with samples at cell centers.
+        let size = self.bbox.size();
+        let counts = [self.nx, self.ny, self.nz];
+        let mut base = [0usize; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let x = (p[axis] - self.bbox.min[axis]) / size[axis];
+            if !(0.0..=1.0).contains(&x) {
+                return 0.0;
+            }
+            let g = (x * counts[axis] as f64 - 0.5).clamp(0.0, (counts[axis] - 1) as f64);
+            base[axis] = (g as usize).min(counts[axis].saturating_sub(2));
+            frac[axis] = if counts[axis] > 1 {
+                g - base[axis] as f64
+            } else {
+                0.0
+            };
+        }
+
+        let mut accum = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0usize; 3];
+            for axis in 0..3 {
+                let step = corner >> axis & 1;
+                index[axis] = (base[axis] + step).min(counts[axis] - 1);
+                weight *= if step == 1 {
+                    frac[axis]
+                } else {
+                    1.0 - frac[axis]
+                };
+            }
+            accum += weight * self.value(index[0], index[1], index[2]);
+        }
+        accum
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, ray_t: RangeInclusive<f64>, rec: &mut HitRecord) -> bool {
+        let Some((t_enter, t_exit)) = span_inside(self.boundary.as_ref(), r, ray_t) else {
+            return false;
+        };
+        let max = self.field.max_density();
+        if max <= 0.0 {
+            return false;
+        }
+
+        // Delta tracking: step through the volume as if it had the max density
+        // everywhere, keeping each tentative collision in proportion to the real
+        // density there.
+        let ray_length = r.direction().length();
+        let mut t = t_enter;
+        loop {
+            t -= (1.0 - random_f64()).ln() / (max * ray_length);
+            if t >= t_exit {
+                return false;
+            }
+            if random_f64() * max < self.field.density(&r.at(t)) {
+                break;
+            }
+        }
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.normal = Vec3::build(1.0, 0.0, 0.0); // arbitrary
+        rec.front_face = true; // also arbitrary
+        rec.mat = Arc::clone(&self.phase_function);
+        rec.object_id = 0;
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    /// Shadow rays pass through the medium, dimmed by its transmittance.
+    fn shadow_hit(&self, _r: &Ray, _ray_t: RangeInclusive<f64>, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    /// Returns an unbiased estimate, by ratio tracking, of the fraction of light that
+    /// passes through the medium along a Ray over the interval ray_t.
+    fn transmittance(&self, r: &Ray, ray_t: RangeInclusive<f64>) -> f64 {
+        let Some((t_enter, t_exit)) = span_inside(self.boundary.as_ref(), r, ray_t) else {
+            return 1.0;
+        };
+        let max = self.field.max_density();
+        if max <= 0.0 {
+            return 1.0;
+        }
+
+        let ray_length = r.direction().length();
+        let mut transmittance = 1.0;
+        let mut t = t_enter;
+        loop {
+            t -= (1.0 - random_f64()).ln() / (max * ray_length);
+            if t >= t_exit {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.field.density(&r.at(t)) / max;
+        }
+    }
+}
+
+impl Display for NoiseDensity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "noise density {} at scale {}", self.density, self.scale)
+    }
+}
+
+impl Display for GridDensity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{}x{} density grid in {}",
+            self.nx, self.ny, self.nz, self.bbox
+        )
+    }
+}
+
+impl Display for HeterogeneousMedium {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "medium of {} in {}", self.field, self.boundary)
+    }
+}
+
+impl NoiseDensity {
+    /// Builds a new NoiseDensity of depth octaves of seeded Perlin turbulence, scaled
+    /// to reach density where the turbulence is strongest.
+    pub fn build(seed: u64, scale: f64, depth: i32, density: f64) -> Self {
+        Self {
+            noise: Perlin::build(seed),
+            scale,
+            depth,
+            density,
+        }
+    }
+}
+
+impl GridDensity {
+    /// Builds a new GridDensity from nx * ny * nz samples, x varying fastest, spanning
+    /// the box bbox. Fails if the number of samples does not match, or if any sample
+    /// is negative or NaN.
+    pub fn build(
+        nx: usize,
+        ny: usize,
+        nz: usize,
+        values: Vec<f64>,
+        bbox: Aabb,
+    ) -> io::Result<Self> {
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz));
+        if count != Some(values.len()) {
+            return Err(invalid_data("grid size mismatch"));
+        }
+        if values.iter().any(|v| v.is_nan() || *v < 0.0) {
+            return Err(invalid_data("grid densities must not be negative or NaN"));
+        }
+        let max = values.iter().copied().fold(0.0, f64::max);
+        Ok(Self {
+            nx,
+            ny,
+            nz,
+            values,
+            bbox,
+            max,
+        })
+    }
+
+    /// Loads a GridDensity from a Mitsuba-style binary `.vol` file of 32-bit floats.
+    /// Files with several channels per sample keep the first.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut input = BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic[..3] != b"VOL" || magic[3] != 3 {
+            return Err(invalid_data("not a version 3 VOL file"));
+        }
+        let mut read_i32 = || -> io::Result<i32> {
+            let mut bytes = [0u8; 4];
+            input.read_exact(&mut bytes)?;
+            Ok(i32::from_le_bytes(bytes))
+        };
+        if read_i32()? != 1 {
+            return Err(invalid_data("only 32-bit float VOL data is supported"));
+        }
+        let dims = [read_i32()?, read_i32()?, read_i32()?, read_i32()?];
+        if dims.iter().any(|&d| d < 1) {
+            return Err(invalid_data("bad VOL dimensions"));
+        }
+        let [nx, ny, nz, channels] = dims.map(|d| d as usize);
+
+        let mut read_f32 = || -> io::Result<f64> {
+            let mut bytes = [0u8; 4];
+            input.read_exact(&mut bytes)?;
+            Ok(f32::from_le_bytes(bytes) as f64)
+        };
+        let mut corners = [0.0; 6];
+        for c in &mut corners {
+            *c = read_f32()?;
+        }
+        let bbox = Aabb::build(
+            Point3::build(corners[0], corners[1], corners[2]),
+            Point3::build(corners[3], corners[4], corners[5]),
+        );
+
+        // The header takes 48 bytes, and each channel of each sample 4 more.
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz));
+        let size = count.and_then(|n| n.checked_mul(channels)?.checked_mul(4));
+        let (Some(count), true) = (count, size.is_some_and(|s| s as u64 <= file_size - 48)) else {
+            return Err(invalid_data("VOL file is too short for its dimensions"));
+        };
+
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(read_f32()?);
+            for _ in 1..channels {
+                read_f32()?;
+            }
+        }
+        Self::build(nx, ny, nz, values, bbox)
+    }
+
+    /// Returns the sample at grid location i, j, k.
+    fn value(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.values[(k * self.ny + j) * self.nx + i]
+    }
+}
+
+impl HeterogeneousMedium {
+    /// Builds a new HeterogeneousMedium filling boundary with the density field, and
+    /// scattering with the given phase function material.
+    pub fn build(
+        boundary: Arc<dyn Hittable>,
+        field: Arc<dyn DensityField>,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            field,
+            phase_function,
+        }
+    }
+}
+
+impl HeightFog {
+    /// Builds a new HeightFog.
+    pub fn build(density: f64, falloff: f64, height: f64, color: Color) -> Self {
+        Self {
+            density,
+            falloff,
+            height,
+            color,
+        }
+    }
+
+    /// Returns the radiance seen along a Ray through the fog, given the radiance
+    /// arriving from its end at t (infinite for rays that escape the scene).
+    pub fn apply(&self, r: &Ray, t: f64, radiance: Color) -> Color {
+        let transmittance = self.transmittance(r, t);
+        transmittance * radiance + (1.0 - transmittance) * self.color
+    }
+
+    /// Returns the fraction of light passing through the fog along a Ray from its
+    /// origin to t.
+    pub fn transmittance(&self, r: &Ray, t: f64) -> f64 {
+        (-self.optical_depth(r, t)).exp()
+    }
+
+    /// Returns the integral of the fog density along a Ray from its origin to t.
+    fn optical_depth(&self, r: &Ray, t: f64) -> f64 {
+        let length = r.direction().length();
+        let base = self.density * (-self.falloff * (r.orig.y() - self.height)).exp();
+
+        // With density a * e^(-b * (y0 + s * dy)), the integral over s from 0 to t is
+        // a * e^(-b * y0) * (1 - e^(-b * dy * t)) / (b * dy), or just a * e^(-b * y0) * t
+        // when the fog doesn't change along the ray.
+        let rate = self.falloff * r.direction().y();
+        if rate.abs() < 1e-9 {
+            return base * t * length;
+        }
+        let depth = base * -(-rate * t).exp_m1() / rate * length;
+        if depth.is_nan() {
+            f64::INFINITY
+        } else {
+            depth
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Isotropic;
+    use crate::sphere::Sphere;
+
+    #[test]
+    fn test_grid_density() {
+        let bbox = Aabb::build(Point3::new(), Point3::build(2.0, 1.0, 1.0));
+        let grid = GridDensity::build(2, 1, 1, vec![1.0, 3.0], bbox).unwrap();
+        assert_eq!(grid.max_density(), 3.0);
+        assert_eq!(grid.density(&Point3::build(0.2, 0.5, 0.5)), 1.0);
+        assert_eq!(grid.density(&Point3::build(1.0, 0.5, 0.5)), 2.0);
+        assert_eq!(grid.density(&Point3::build(1.8, 0.5, 0.5)), 3.0);
+        assert_eq!(grid.density(&Point3::build(2.5, 0.5, 0.5)), 0.0);
+
+        assert!(GridDensity::build(2, 1, 1, vec![1.0], bbox).is_err());
+        assert!(GridDensity::build(2, 1, 1, vec![1.0, -3.0], bbox).is_err());
+        assert!(GridDensity::build(2, 1, 1, vec![1.0, f64::NAN], bbox).is_err());
+    }
+
+    #[test]
+    fn test_grid_file() {
+        let path = std::env::temp_dir().join(format!("grid-{}.vol", std::process::id()));
+        let mut bytes = b"VOL\x03".to_vec();
+        for value in [1, 2, 1, 1, 1] {
+            bytes.extend_from_slice(&i32::to_le_bytes(value));
+        }
+        for value in [0.0, 0.0, 0.0, 2.0, 1.0, 1.0, 0.5, 4.0] {
+            bytes.extend_from_slice(&f32::to_le_bytes(value));
+        }
+        std::fs::write(&path, &bytes).unwrap();
+        let grid = GridDensity::load(&path).unwrap();
+        assert_eq!(grid.max_density(), 4.0);
+
+        // Dimensions whose sample count overflows, or that the file cannot hold.
+        for dims in [[i32::MAX; 3], [1000, 1000, 1000]] {
+            for (d, value) in dims.iter().enumerate() {
+                bytes[8 + 4 * d..12 + 4 * d].copy_from_slice(&i32::to_le_bytes(*value));
+            }
+            std::fs::write(&path, &bytes).unwrap();
+            let err = GridDensity::load(&path).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_transmittance() {
+        // A slab of density 1 and thickness 1 in the middle of a unit-radius sphere:
+        // the exact transmittance across it is e^-1.
+        let bbox = Aabb::build(
+            Point3::build(-1.0, -1.0, -0.5),
+            Point3::build(1.0, 1.0, 0.5),
+        );
+        let field = Arc::new(GridDensity::build(1, 1, 1, vec![1.0], bbox).unwrap());
+        let phase = Arc::new(Isotropic::build(1.0, 1.0, 1.0));
+        let boundary = Arc::new(Sphere::build(Point3::new(), 1.0, phase.clone()));
+        let medium = HeterogeneousMedium::build(boundary, field, phase);
+
+        let r = Ray::build(Point3::build(0.0, 0.0, 5.0), Vec3::build(0.0, 0.0, -1.0));
+        let n = 20000;
+        let estimate = (0..n)
+            .map(|_| medium.transmittance(&r, 0.0..=f64::INFINITY))
+            .sum::<f64>()
+            / n as f64;
+        assert!((estimate - (-1.0_f64).exp()).abs() < 0.02, "{}", estimate);
+
+        let mut rec = HitRecord::new();
+        let escaped = (0..n)
+            .filter(|_| !medium.hit(&r, 0.0..=f64::INFINITY, &mut rec))
+            .count() as f64
+            / n as f64;
+        assert!((escaped - (-1.0_f64).exp()).abs() < 0.02, "{}", escaped);
+    }
+
+    #[test]
+    fn test_height_fog() {
+        let fog = HeightFog::build(0.5, 1.0, 0.0, Color::build(1.0, 1.0, 1.0));
+        let level = Ray::build(Point3::new(), Vec3::build(2.0, 0.0, 0.0));
+        let up = Ray::build(Point3::new(), Vec3::build(0.0, 1.0, 0.0));
+        let down = Ray::build(Point3::new(), Vec3::build(0.0, -1.0, 0.0));
+
+        assert!((fog.optical_depth(&level, 1.0) - 1.0).abs() < 1e-12);
+        assert!((fog.optical_depth(&up, f64::INFINITY) - 0.5).abs() < 1e-12);
+        assert_eq!(fog.optical_depth(&down, f64::INFINITY), f64::INFINITY);
+        assert_eq!(
+            fog.apply(&down, f64::INFINITY, Color::new()),
+            Color::build(1.0, 1.0, 1.0)
+        );
+    }
+}